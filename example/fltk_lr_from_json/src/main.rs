@@ -1,7 +1,7 @@
 use fltk::{app::*, button::*, frame::*, window::*};
 use quadoculars::LiveJson;
 use serde::Deserialize;
-use std::{env, path::PathBuf, thread};
+use std::{env, path::PathBuf, thread, time::Duration};
 
 #[derive(Debug, Clone, Copy)]
 pub enum Message {
@@ -92,7 +92,7 @@ fn main() -> anyhow::Result<()> {
 
             // Start mutate here
             // or 'retry: while let Ok(watching) = btns.reload_from_json(&json) {...
-            while let Ok(watching) = btns.reload_from_json(&json, 0.6) {
+            while let Ok(watching) = btns.reload_from_json(&json, Duration::from_millis(600)) {
                 // Set all while mutating.
                 if watching {
                     btn_inc.set_label(&format!("{} +", btns.btn_inc_label));
@@ -105,11 +105,11 @@ fn main() -> anyhow::Result<()> {
         let tx = _tx;
         let mut btns = _btns;
         let ron = _ron;
-        // while btns.reload_from_ron(&ron, 0.6)? {
+        // while btns.reload_from_ron(&ron, Duration::from_millis(600))? {
         //     let btns_ = btns.clone();
         //     tx.send(btns_)?;
         // }
-        while let Ok(watching) = btns.reload_from_ron(&ron, 0.6) {
+        while let Ok(watching) = btns.reload_from_ron(&ron, Duration::from_millis(600)) {
             if watching {
                 let btns_ = btns.clone();
                 tx.send(btns_)?;
@@ -1,6 +1,6 @@
 use quadoculars::LiveRon;
 use serde::Deserialize;
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, time::Duration};
 
 #[derive(Debug, Clone, Deserialize)]
 struct Btns {
@@ -42,7 +42,7 @@ fn main() -> anyhow::Result<()> {
     println!("btn_dec_pos.x: {}", y);
     println!(" ");
 
-    while let Ok(watching) = btns.reload_from_ron(&ron, 0.6) {
+    while let Ok(watching) = btns.reload_from_ron(&ron, Duration::from_millis(600)) {
         if watching {
             let btn_dec_label = btns.btn_dec_label.clone();
             let x = btns.btn_dec_pos.x;
@@ -126,7 +126,7 @@ fn main() -> anyhow::Result<()> {
 //         let tx = _tx;
 //         let mut btns = _btns;
 //         let ron = _ron;
-//         while btns.reload_from_ron(&ron, 0.6)? {
+//         while btns.reload_from_ron(&ron, Duration::from_millis(600))? {
 //             let btns_ = btns.clone();
 //             tx.send(btns_)?;
 //         }
@@ -1,5 +1,5 @@
 use quadoculars::Watch;
-use std::{env, path::PathBuf};
+use std::{env, path::PathBuf, time::Duration};
 
 fn main() -> anyhow::Result<()> {
     let mut json = PathBuf::new();
@@ -10,7 +10,7 @@ fn main() -> anyhow::Result<()> {
         json.push("src/json/Btns.json");
     }
 
-    let watch = Watch::new().set_timeout(0.6);
+    let watch = Watch::new().set_timeout(Duration::from_millis(600));
     let mut val = serde_json::json!({});
     watch.json_val_init(&json, &mut val);
 
@@ -1,5 +1,5 @@
 use quadoculars::{Fstate, Watch};
-use std::{env, path::PathBuf, str::FromStr, sync::mpsc::channel};
+use std::{env, path::PathBuf, str::FromStr, sync::mpsc::channel, time::Duration};
 
 fn main() -> anyhow::Result<()> {
     let mut vec_files = Vec::new();
@@ -23,7 +23,7 @@ fn main() -> anyhow::Result<()> {
     let (tx, rx) = channel();
 
     while let Ok(file_exist) = Watch::new()
-        .set_timeout(0.6)
+        .set_timeout(Duration::from_millis(600))
         .multiple_files(&mut vec_files, tx.clone())
     {
         if !file_exist {
@@ -52,6 +52,8 @@ fn main() -> anyhow::Result<()> {
                         break;
                     }
                 }
+                // only emitted with Watch::set_persistent
+                _ => (),
             }
         }
     }
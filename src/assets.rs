@@ -0,0 +1,450 @@
+//! Asset hot reloading: typed loaders and handles on top of a WatchSet.
+use crate::{live::LiveFiles, Fstate, Sender, Watch, WatchSet, BREAK_POINT, FALSE, TRUE};
+use notify::Result;
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    io::{self, Read},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::spawn,
+    time::Duration,
+};
+
+/// Decodes one kind of asset (texture, shader, sound...) from the content of its file.
+pub trait Loader: Send + Sync + 'static {
+    type Asset: Send + Sync + 'static;
+    type Error: fmt::Display;
+
+    fn load(&self, bytes: &[u8]) -> std::result::Result<Self::Asset, Self::Error>;
+
+    /// Other files the asset is built from (e.g. the textures of a material), relative to the asset's directory.
+    ///
+    /// the asset is reloaded whenever one of them changes, or is reloaded itself if it's an asset too.
+    fn dependencies(&self, _asset: &Self::Asset) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+type Value = Arc<dyn Any + Send + Sync>;
+
+trait Erased: Send + Sync {
+    fn load(&self, bytes: &[u8]) -> std::result::Result<(Value, Vec<PathBuf>), String>;
+}
+
+struct Typed<L>(L);
+
+impl<L: Loader> Erased for Typed<L> {
+    fn load(&self, bytes: &[u8]) -> std::result::Result<(Value, Vec<PathBuf>), String> {
+        match self.0.load(bytes) {
+            Ok(asset) => {
+                let dependencies = self.0.dependencies(&asset);
+                Ok((Arc::new(asset), dependencies))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Untyped identity of an asset, carried by AssetEvent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AssetId(usize);
+
+typed_handle! {
+    /// Typed handle of an asset, get its latest version with AssetServer::get.
+    pub struct Handle<T>(AssetId);
+}
+
+/// What happened to an asset.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AssetEvent {
+    /// decoded again (its file or one of its dependencies changed), get returns the new version.
+    Reloaded(AssetId),
+    /// the file couldn't be read or decoded, get keeps returning the last good version.
+    Failed { id: AssetId, error: String },
+    /// the file is gone, get keeps returning the last good version and the asset reloads once it's back.
+    Removed(AssetId),
+}
+
+struct Slot {
+    path: PathBuf,
+    loader_type: TypeId,
+    loader: Arc<dyn Erased>,
+    value: Option<Value>,
+    dependencies: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct Registry {
+    slots: Vec<Slot>,
+    // dependency (canonical path) -> assets built from it.
+    dependents: HashMap<PathBuf, Vec<AssetId>>,
+}
+
+impl Registry {
+    fn at<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = AssetId> + 'a {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(move |(_, slot)| slot.path == path)
+            .map(|(i, _)| AssetId(i))
+    }
+}
+
+struct Shared {
+    // files are read (decoded, decompressed) as this watch does.
+    watch: Watch,
+    set: WatchSet,
+    tx: Sender<AssetEvent>,
+    registry: Mutex<Registry>,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.watch.open(path)?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Read and decode the asset, swapping it in (and its dependencies) on success.
+    fn decode(&self, id: AssetId) -> bool {
+        let (path, loader) = {
+            let registry = self.lock();
+            let slot = &registry.slots[id.0];
+            (slot.path.clone(), slot.loader.clone())
+        };
+        // decoded without the lock, get keeps answering meanwhile.
+        let decoded = match self.read(&path) {
+            Ok(bytes) => loader.load(&bytes),
+            Err(e) => Err(e.to_string()),
+        };
+        let (value, dependencies) = match decoded {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                warn!(path = %path.display(), error = %error, "asset failed to load");
+                let _ = self.tx.send(AssetEvent::Failed { id, error });
+                return FALSE;
+            }
+        };
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let dependencies: Vec<PathBuf> = dependencies
+            .iter()
+            .map(|dependency| self.watch.env.canonicalize(&dir.join(dependency)))
+            .collect();
+
+        let mut registry = self.lock();
+        let slot = &mut registry.slots[id.0];
+        slot.value = Some(value);
+        let old = mem::replace(&mut slot.dependencies, dependencies.clone());
+        for dependency in old {
+            let unused = match registry.dependents.get_mut(&dependency) {
+                Some(ids) => {
+                    ids.retain(|dependent| *dependent != id);
+                    ids.is_empty()
+                }
+                None => FALSE,
+            };
+            if unused {
+                registry.dependents.remove(&dependency);
+                if registry.at(&dependency).next().is_none() {
+                    self.set.remove(&dependency);
+                }
+            }
+        }
+        for dependency in dependencies {
+            let ids = registry.dependents.entry(dependency.clone()).or_default();
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+            // a missing dependency is the loader's business, it's picked up once the asset reloads.
+            let _ = self.set.add(&dependency);
+        }
+        TRUE
+    }
+
+    /// Reload the assets of the file and, once they decoded fine, everything built from them.
+    fn changed(&self, path: PathBuf) {
+        let mut queue = VecDeque::from(vec![path]);
+        let (mut seen, mut reloaded) = (HashSet::new(), HashSet::new());
+        while let Some(path) = queue.pop_front() {
+            let (assets, dependents) = {
+                let registry = self.lock();
+                let dependents = registry.dependents.get(&path).cloned();
+                (
+                    registry.at(&path).collect::<Vec<_>>(),
+                    dependents.unwrap_or_default(),
+                )
+            };
+            for id in &assets {
+                if seen.insert(*id) && self.reload(*id) {
+                    reloaded.insert(*id);
+                }
+            }
+            // a plain dependency file always cascades, an asset only with a good version.
+            if !assets.is_empty() && !assets.iter().any(|id| reloaded.contains(id)) {
+                continue;
+            }
+            for id in dependents {
+                if seen.insert(id) && self.reload(id) {
+                    reloaded.insert(id);
+                    queue.push_back(self.lock().slots[id.0].path.clone());
+                }
+            }
+        }
+    }
+
+    fn reload(&self, id: AssetId) -> bool {
+        let decoded = self.decode(id);
+        if decoded {
+            debug!(id = id.0, "asset reloaded");
+            let _ = self.tx.send(AssetEvent::Reloaded(id));
+        }
+        decoded
+    }
+
+    fn removed(&self, path: &Path) {
+        let ids: Vec<AssetId> = self.lock().at(path).collect();
+        for id in ids {
+            let _ = self.tx.send(AssetEvent::Removed(id));
+        }
+    }
+}
+
+// stops the reload thread and the watchers with the last AssetServer.
+struct Alive {
+    shared: Arc<Shared>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Drop for Alive {
+    fn drop(&mut self) {
+        self.closed.store(TRUE, Relaxed);
+        self.shared.set.clear();
+    }
+}
+
+/// Registry of hot reloaded assets, see Watch::asset_server.
+///
+/// Files are read and decoded by their Loader on a background thread, get always returns the latest version that decoded fine.
+/// The Loader gets the bytes as the watch reads them: decoded (Watch::set_decoder) and decompressed (Watch::set_decompress) first.
+/// Clones share the same registry.
+#[derive(Clone)]
+pub struct AssetServer {
+    shared: Arc<Shared>,
+    _alive: Arc<Alive>,
+}
+
+impl AssetServer {
+    pub(crate) fn new(watch: &Watch, tx: Sender<AssetEvent>) -> AssetServer {
+        let files = LiveFiles::new(watch);
+        let shared = Arc::new(Shared {
+            watch: watch.clone(),
+            set: files.set().clone(),
+            tx,
+            registry: Mutex::default(),
+        });
+        let closed = Arc::new(AtomicBool::new(FALSE));
+        let (reloader, stop) = (shared.clone(), closed.clone());
+        let period = (watch.timeout / BREAK_POINT as u32).max(Duration::from_millis(1));
+        spawn(move || {
+            while !stop.load(Relaxed) {
+                match files.next(period) {
+                    Some(Fstate::Changed(path)) => reloader.changed(path),
+                    // the last good version is kept, Removed tells the application it's stale.
+                    Some(Fstate::Removed(path)) | Some(Fstate::NotFound(path)) => {
+                        reloader.removed(&path)
+                    }
+                    _ => (),
+                }
+            }
+        });
+        Self {
+            shared: shared.clone(),
+            _alive: Arc::new(Alive { shared, closed }),
+        }
+    }
+
+    /// Load the asset with the loader and keep it up to date, the same handle is returned if it's already loaded with the same loader.
+    ///
+    /// Err if the file doesn't exist or can't be watched. A file that doesn't decode still gets a handle
+    /// (AssetEvent::Failed is sent, get returns None until it decodes fine).
+    pub fn load<L: Loader, P: AsRef<Path>>(&self, path: P, loader: L) -> Result<Handle<L::Asset>> {
+        let path = path.as_ref();
+        if !self.shared.watch.env.is_file(path) {
+            return Err(notify::Error::path_not_found().add_path(path.to_path_buf()));
+        }
+        let key = self.shared.watch.env.canonicalize(path);
+        let loader_type = TypeId::of::<L>();
+        let id = {
+            let mut registry = self.shared.lock();
+            let loaded = registry
+                .slots
+                .iter()
+                .position(|slot| slot.path == key && slot.loader_type == loader_type);
+            if let Some(i) = loaded {
+                return Ok(Handle::new(AssetId(i)));
+            }
+            registry.slots.push(Slot {
+                path: key.clone(),
+                loader_type,
+                loader: Arc::new(Typed(loader)),
+                value: None,
+                dependencies: Vec::new(),
+            });
+            AssetId(registry.slots.len() - 1)
+        };
+        self.shared.set.add(&key)?;
+        self.shared.decode(id);
+        Ok(Handle::new(id))
+    }
+
+    /// The latest version of the asset that decoded fine, None if none did yet.
+    pub fn get<T: Send + Sync + 'static>(&self, handle: &Handle<T>) -> Option<Arc<T>> {
+        let value = self.shared.lock().slots.get(handle.id.0)?.value.clone()?;
+        value.downcast().ok()
+    }
+
+    /// The file of the asset (canonical path).
+    pub fn path(&self, id: AssetId) -> Option<PathBuf> {
+        self.shared
+            .lock()
+            .slots
+            .get(id.0)
+            .map(|slot| slot.path.clone())
+    }
+
+    /// The files the asset was built from as of its latest version (canonical paths).
+    pub fn dependencies(&self, id: AssetId) -> Vec<PathBuf> {
+        match self.shared.lock().slots.get(id.0) {
+            Some(slot) => slot.dependencies.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualFs;
+    use cfg_if::cfg_if;
+    use std::sync::atomic::AtomicUsize;
+    cfg_if! {
+       if #[cfg(feature = "crossbeam_channel")] {
+           use crossbeam_channel::{unbounded as channel, Receiver};
+       } else if #[cfg(feature = "flume_channel")] {
+           use flume::{unbounded as channel, Receiver};
+       } else {
+           use std::sync::mpsc::{channel, Receiver};
+       }
+    }
+
+    struct Text;
+
+    impl Loader for Text {
+        type Asset = String;
+        type Error = std::string::FromUtf8Error;
+
+        fn load(&self, bytes: &[u8]) -> std::result::Result<String, Self::Error> {
+            String::from_utf8(bytes.to_vec())
+        }
+    }
+
+    // one texture path per line, counts its decodes.
+    struct Material(Arc<AtomicUsize>);
+
+    impl Loader for Material {
+        type Asset = Vec<String>;
+        type Error = std::str::Utf8Error;
+
+        fn load(&self, bytes: &[u8]) -> std::result::Result<Vec<String>, Self::Error> {
+            self.0.fetch_add(1, Relaxed);
+            Ok(std::str::from_utf8(bytes)?
+                .lines()
+                .map(String::from)
+                .collect())
+        }
+
+        fn dependencies(&self, textures: &Vec<String>) -> Vec<PathBuf> {
+            textures.iter().map(PathBuf::from).collect()
+        }
+    }
+
+    fn next(rx: &Receiver<AssetEvent>) -> AssetEvent {
+        rx.recv_timeout(Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn test_asset_server() {
+        let fs = VirtualFs::new();
+        let (grass, material_file) = (
+            PathBuf::from("assets/grass.txt"),
+            PathBuf::from("assets/grass.mat"),
+        );
+        fs.write(&grass, "green");
+        fs.write(&material_file, "grass.txt");
+
+        let (tx, rx) = channel();
+        let assets = Watch::new().set_fs(&fs).asset_server(tx);
+        let decodes = Arc::new(AtomicUsize::new(0));
+        let texture = assets.load(&grass, Text).unwrap();
+        let material = assets
+            .load(&material_file, Material(decodes.clone()))
+            .unwrap();
+        assert_eq!(assets.load("assets/./grass.txt", Text).unwrap(), texture);
+        assert!(assets.load("assets/missing.txt", Text).is_err());
+        assert_eq!(*assets.get(&texture).unwrap(), "green");
+        assert_eq!(*assets.get(&material).unwrap(), vec!["grass.txt"]);
+        assert_eq!(
+            assets.dependencies(material.id()),
+            vec![assets.path(texture.id()).unwrap()]
+        );
+        fs.wait_watchers(2);
+
+        // the material reloads after the texture it depends on.
+        fs.write(&grass, "greener");
+        assert_eq!(next(&rx), AssetEvent::Reloaded(texture.id()));
+        assert_eq!(next(&rx), AssetEvent::Reloaded(material.id()));
+        assert_eq!(*assets.get(&texture).unwrap(), "greener");
+        assert_eq!(decodes.load(Relaxed), 2);
+
+        // a broken texture keeps its last good version and doesn't cascade.
+        fs.write(&grass, [0xff, 0xfe]);
+        assert!(matches!(next(&rx), AssetEvent::Failed { id, .. } if id == texture.id()));
+        assert_eq!(*assets.get(&texture).unwrap(), "greener");
+        assert_eq!(decodes.load(Relaxed), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "live_json")]
+    fn test_decoded_assets() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("assets/motd.txt.rev");
+        fs.write(&file, "olleh");
+
+        let (tx, rx) = channel();
+        let assets = Watch::new()
+            .set_fs(&fs)
+            .set_timeout(Duration::ZERO)
+            .set_decoder(|_: &Path, mut bytes: Vec<u8>| {
+                bytes.reverse();
+                Ok(bytes)
+            })
+            .asset_server(tx);
+        let motd = assets.load(&file, Text).unwrap();
+        assert_eq!(*assets.get(&motd).unwrap(), "hello");
+        fs.wait_watchers(1);
+
+        fs.write(&file, "dlrow");
+        assert_eq!(next(&rx), AssetEvent::Reloaded(motd.id()));
+        assert_eq!(*assets.get(&motd).unwrap(), "world");
+    }
+}
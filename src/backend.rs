@@ -0,0 +1,352 @@
+use crate::{
+    env::{deadline, Env, Stamp, Subscription},
+    stats::Counters,
+    Watch, FALSE, TRUE, ZERO,
+};
+use notify::{
+    event::{AccessKind, AccessMode, ModifyKind, RenameMode},
+    Event, EventKind, Result,
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Swap/backup files written by editors next to the real file, never reported.
+const SWAP_SUFFIXES: [&str; 4] = [".swp", ".swx", "~", ".tmp"];
+
+/// How often Backend::Auto checks the file metadata behind the native watcher's back.
+const PROBE: Duration = Duration::from_secs(1);
+
+/// Where the watcher gets its change notifications from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Native notifications (inotify, FSEvents, ReadDirectoryChangesW) on the parent directory.
+    #[default]
+    Native,
+    /// Poll the file metadata (mtime and size) every given interval, for NFS/SMB/FUSE mounts
+    /// and bind mounts where native notifications never fire.
+    Poll(Duration),
+    /// Start native, probe the metadata every second and fall back to polling (per path)
+    /// when a change went unreported and no native event arrived until the next probe either.
+    Auto,
+}
+
+#[inline]
+pub(crate) fn is_swap_file(path: &Path) -> bool {
+    match path.file_name() {
+        Some(name) => {
+            let name = name.to_string_lossy();
+            SWAP_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        }
+        None => FALSE,
+    }
+}
+
+/// Check whether the event (re)writes the watched file, either in place or by renaming a temp file over it.
+#[inline]
+pub(crate) fn is_written(event: &Event, file: &Path) -> bool {
+    let target = match event.kind {
+        EventKind::Create(_)
+        | EventKind::Modify(ModifyKind::Any)
+        | EventKind::Modify(ModifyKind::Data(_))
+        | EventKind::Access(AccessKind::Close(AccessMode::Write)) => event.paths.first(),
+        // (from, to), only the destination matters.
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => event.paths.last(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To))
+        | EventKind::Modify(ModifyKind::Name(RenameMode::Any)) => event.paths.first(),
+        _ => None,
+    };
+    match target {
+        // the watched file itself, even if its own name looks like a swap file (`out/data.tmp`).
+        Some(path) if path.file_name() == file.file_name() => TRUE,
+        Some(path) => {
+            if is_swap_file(path) {
+                trace!(path = ?path, "swap file ignored");
+            }
+            FALSE
+        }
+        None => FALSE,
+    }
+}
+
+/// Check whether the event removes the watched file (or renames it away).
+#[inline]
+pub(crate) fn is_removed(event: &Event, file: &Path) -> bool {
+    match event.kind {
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => event
+            .paths
+            .first()
+            .map_or(FALSE, |path| path.file_name() == file.file_name()),
+        _ => FALSE,
+    }
+}
+
+/// Nested links followed at most, like the kernel's limit (also breaks loops).
+const MAX_LINKS: usize = 40;
+
+#[inline]
+fn dir_of(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// The file, every symlink hop after it and the final target (with directory symlinks resolved).
+pub(crate) fn resolve(env: &dyn Env, file: &Path) -> Vec<PathBuf> {
+    let mut chain = vec![file.to_path_buf()];
+    for _ in ZERO..MAX_LINKS {
+        let current = &chain[chain.len() - 1];
+        let next = match env.read_link(current) {
+            Some(target) if target.is_relative() => dir_of(current).join(target),
+            Some(target) => target,
+            None => break,
+        };
+        if chain.contains(&next) {
+            break;
+        }
+        chain.push(next);
+    }
+    let target = env.canonicalize(&chain[chain.len() - 1]);
+    chain.push(target);
+    chain
+}
+
+/// Directories to watch for the chain, each (real) directory once.
+fn dirs(env: &dyn Env, chain: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    for path in chain {
+        let dir = env.canonicalize(&dir_of(path));
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Check whether the event changes the watched file's metadata (permissions, ownership...).
+#[inline]
+pub(crate) fn is_touched(event: &Event, file: &Path) -> bool {
+    match event.kind {
+        EventKind::Modify(ModifyKind::Metadata(_)) => event
+            .paths
+            .first()
+            .map_or(FALSE, |path| path.file_name() == file.file_name()),
+        _ => FALSE,
+    }
+}
+
+/// Change notifications of a single watched file.
+pub(crate) struct Source {
+    env: Arc<dyn Env>,
+    counters: Arc<Counters>,
+    file: PathBuf,
+    // follow_symlinks only: the resolved chain and the directories watched for it.
+    chain: Option<Vec<PathBuf>>,
+    dirs: Vec<PathBuf>,
+    // set_track_metadata only: return early on metadata events.
+    track_metadata: bool,
+    subscription: Option<Box<dyn Subscription>>,
+    // polling (or probing for Auto) interval.
+    interval: Option<Duration>,
+    next_poll: Instant,
+    stamp: Stamp,
+    // Auto only: a native event arrived since the last probe.
+    seen_native: bool,
+    // Auto only: the last probe caught a change the native watcher didn't report.
+    missed: bool,
+}
+
+impl Source {
+    pub(crate) fn new(file: &Path, opts: &Watch) -> Result<Source> {
+        let env = opts.env.clone();
+        // watch the parent directory rather than the file itself,
+        // so the watch survives editors that save by renaming a temp file over the original (new inode).
+        // following symlinks, the directories of every hop and of the target are watched as well.
+        let chain = match opts.follow_symlinks {
+            TRUE => Some(resolve(&*env, file)),
+            FALSE => None,
+        };
+        let dirs = match &chain {
+            Some(chain) => dirs(&*env, chain),
+            None => vec![dir_of(file)],
+        };
+        let subscribe = || -> Result<Box<dyn Subscription>> {
+            let mut subscription = env.subscribe(&dirs[0])?;
+            for dir in &dirs[1..] {
+                subscription.watch(dir)?;
+            }
+            Ok(subscription)
+        };
+        let (subscription, interval) = match opts.backend {
+            Backend::Poll(interval) => (None, Some(interval)),
+            Backend::Native => (Some(subscribe()?), None),
+            Backend::Auto => (Some(subscribe()?), Some(PROBE)),
+        };
+        Ok(Self {
+            file: file.to_path_buf(),
+            chain,
+            dirs,
+            track_metadata: opts.track_metadata,
+            subscription,
+            interval,
+            next_poll: deadline(env.now(), interval.unwrap_or_default()),
+            stamp: env.stamp(file),
+            seen_native: FALSE,
+            missed: FALSE,
+            env,
+            counters: opts.counters.clone(),
+        })
+    }
+
+    /// Whether the native watcher has been dropped in favour of polling.
+    #[inline]
+    pub(crate) fn is_polling(&self) -> bool {
+        self.subscription.is_none()
+    }
+
+    /// Wait up to the timeout for the file to be (re)written, returns early with true if it is,
+    /// or with false as soon as it's removed (or its metadata changed, set_track_metadata only).
+    pub(crate) fn wait(&mut self, timeout: Duration) -> Result<bool> {
+        let deadline = deadline(self.env.now(), timeout);
+        loop {
+            if self.env.now() >= deadline {
+                return Ok(FALSE);
+            }
+            let until = match self.interval {
+                Some(_) => deadline.min(self.next_poll),
+                None => deadline,
+            };
+            match &mut self.subscription {
+                Some(subscription) => {
+                    if let Some(event) = subscription.recv_until(until) {
+                        let event = event?;
+                        self.counters.event();
+                        // any event at all shows native notifications work for this directory.
+                        self.seen_native = TRUE;
+                        trace!(kind = ?event.kind, paths = ?event.paths, "notify event");
+                        let chain = match &self.chain {
+                            Some(chain) => chain.as_slice(),
+                            None => std::slice::from_ref(&self.file),
+                        };
+                        let written = chain.iter().any(|path| is_written(&event, path));
+                        let removed = chain.iter().any(|path| is_removed(&event, path))
+                            || (self.track_metadata
+                                && chain.iter().any(|path| is_touched(&event, path)));
+                        if written || self.relink()? {
+                            self.stamp = self.env.stamp(&self.file);
+                            return Ok(TRUE);
+                        }
+                        if removed {
+                            self.stamp = self.env.stamp(&self.file);
+                            return Ok(FALSE);
+                        }
+                    }
+                }
+                None => self.env.sleep_until(until),
+            }
+            if self.interval.is_some() && self.env.now() >= self.next_poll && self.poll() {
+                return Ok(TRUE);
+            }
+        }
+    }
+
+    /// Re-resolve the symlink chain (follow_symlinks only), true if a link was repointed.
+    /// the watched directories follow the new chain.
+    fn relink(&mut self) -> Result<bool> {
+        let chain = match &self.chain {
+            Some(chain) => chain,
+            None => return Ok(FALSE),
+        };
+        let resolved = resolve(&*self.env, &self.file);
+        if resolved == *chain {
+            return Ok(FALSE);
+        }
+        debug!(target = ?resolved.last(), "symlink repointed");
+        let dirs = dirs(&*self.env, &resolved);
+        let watched = &self.dirs;
+        if let Some(subscription) = &mut self.subscription {
+            for dir in watched.iter().filter(|dir| !dirs.contains(dir)) {
+                subscription.unwatch(dir);
+            }
+            for dir in dirs.iter().filter(|dir| !watched.contains(dir)) {
+                // nothing to watch yet for a dangling link.
+                let _ = subscription.watch(dir);
+            }
+        }
+        self.chain = Some(resolved);
+        self.dirs = dirs;
+        Ok(TRUE)
+    }
+
+    /// Compare the metadata against the last poll.
+    ///
+    /// Auto falls back to polling once a change went unreported and no native event at all showed up
+    /// until the next probe either, a late or lost event alone doesn't downgrade a working native watcher.
+    fn poll(&mut self) -> bool {
+        let interval = self.interval.unwrap_or(PROBE);
+        self.next_poll = deadline(self.env.now(), interval);
+        let stamp = self.env.stamp(&self.file);
+        let changed = stamp != self.stamp;
+        self.stamp = stamp;
+        if !self.is_polling() {
+            if self.seen_native {
+                self.missed = FALSE;
+            } else if self.missed {
+                // native notifications don't work for this path (NFS, SMB, FUSE...), poll from now on.
+                warn!("change went unreported by the native watcher, falling back to polling");
+                self.subscription = None;
+            } else if changed {
+                debug!("change went unreported by the native watcher, probing again");
+                self.missed = TRUE;
+            }
+        }
+        self.seen_native = FALSE;
+        changed
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use crate::{testing::VirtualFs, Fstate, Watch};
+    use cfg_if::cfg_if;
+    use std::path::PathBuf;
+    cfg_if! {
+       if #[cfg(feature = "crossbeam_channel")] {
+           use crossbeam_channel::unbounded as channel;
+       } else if #[cfg(feature = "flume_channel")] {
+           use flume::unbounded as channel;
+       } else {
+           use std::sync::mpsc::channel;
+       }
+    }
+
+    #[test]
+    fn test_swap_files() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("out/data.json");
+        let tmp = PathBuf::from("out/data.tmp");
+        fs.write(&file, "{}");
+        fs.write(&tmp, "1");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new().set_fs(&fs);
+        assert!(watch.single_file(&file, tx.clone()).unwrap());
+        assert!(watch.single_file(&tmp, tx).unwrap());
+        fs.wait_watchers(2);
+
+        // a swap file next to the watched file isn't a write to it.
+        fs.write("out/data.json.swp", "{\"a\": 1}");
+        fs.write("out/data.json~", "{}");
+        assert!(rx.try_recv().is_err());
+
+        // a watched file named like a swap file still reports its own writes.
+        fs.write(&tmp, "2");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(path)) if path == tmp));
+        fs.write(&file, "{\"a\": 2}");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(path)) if path == file));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use std::{ffi::OsString, fmt, time::Duration};
+
+pub const USAGE: &str = "\
+Run a command whenever the content of the watched files changes.
+
+USAGE:
+    quadoculars [OPTIONS] -w <PATH|GLOB>... -- <COMMAND> [ARGS]...
+    quadoculars events [OPTIONS] -w <PATH|GLOB>...
+
+SUBCOMMANDS:
+    events                   print every file state change as one JSON object per line instead of running a command:
+                             {\"path\": \"a.ron\", \"kind\": \"changed\", \"timestamp\": <unix millis>, \"size\": 12, \"hash\": \"..\"}
+                             kind is changed, removed, created, not_found or metadata_changed (--metadata)
+
+OPTIONS:
+    -w, --watch <PATH|GLOB>  file, directory (watched recursively) or glob to watch, repeatable,
+                             expanded once on start: files created later aren't picked up
+    -c, --clear              clear the screen before each run
+    -r, --restart            restart the command on change instead of queueing a run after it exits
+    -s, --signal <SIGNAL>    signal sent to the command on restart (default SIGTERM, unix only)
+    -p, --postpone           wait for the first change instead of running the command on start
+    -d, --debounce <MS>      collect changes for this long before acting on them (default 50)
+        --poll <MS>          poll the files every MS milliseconds instead of native notifications
+        --size               (events) add the file size in bytes
+        --hash               (events) add the 64-bit FNV-1a hash of the content, as 16 hex digits
+        --metadata           (events) also report permission, ownership, mtime and size changes
+    -h, --help               print this help
+    -V, --version            print the version
+";
+
+/// Command line options.
+#[derive(Debug, PartialEq)]
+pub struct Args {
+    pub watch: Vec<String>,
+    pub clear: bool,
+    pub restart: bool,
+    pub signal: i32,
+    pub postpone: bool,
+    pub debounce: Duration,
+    pub poll: Option<Duration>,
+    pub command: Vec<OsString>,
+    pub size: bool,
+    pub hash: bool,
+    pub metadata: bool,
+}
+
+/// What the command line asks for.
+#[derive(Debug, PartialEq)]
+pub enum Parsed {
+    Run(Args),
+    Events(Args),
+    Help,
+    Version,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Error(pub String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// SIGTERM, same value on every unix.
+const SIGTERM: i32 = 15;
+
+/// Parse a signal name (TERM, SIGTERM...) or number.
+pub fn parse_signal(signal: &str) -> Option<i32> {
+    if let Ok(number) = signal.parse::<i32>() {
+        return if number > 0 { Some(number) } else { None };
+    }
+    let name = signal.to_ascii_uppercase();
+    let name = name.strip_prefix("SIG").unwrap_or(&name);
+    let number = match name {
+        "HUP" => 1,
+        "INT" => 2,
+        "QUIT" => 3,
+        "KILL" => 9,
+        "TERM" => SIGTERM,
+        #[cfg(target_os = "linux")]
+        "USR1" => 10,
+        #[cfg(target_os = "linux")]
+        "USR2" => 12,
+        #[cfg(all(unix, not(target_os = "linux")))]
+        "USR1" => 30,
+        #[cfg(all(unix, not(target_os = "linux")))]
+        "USR2" => 31,
+        _ => return None,
+    };
+    Some(number)
+}
+
+fn millis(option: &str, value: &str) -> Result<Duration, Error> {
+    value.parse().map(Duration::from_millis).map_err(|_| {
+        Error(format!(
+            "invalid value '{}' for {}, expected milliseconds",
+            value, option
+        ))
+    })
+}
+
+pub fn parse<I: IntoIterator<Item = OsString>>(args: I) -> Result<Parsed, Error> {
+    let mut parsed = Args {
+        watch: Vec::new(),
+        clear: false,
+        restart: false,
+        signal: SIGTERM,
+        postpone: false,
+        debounce: Duration::from_millis(50),
+        poll: None,
+        command: Vec::new(),
+        size: false,
+        hash: false,
+        metadata: false,
+    };
+    let mut args = args.into_iter().peekable();
+    let events = args.peek().is_some_and(|arg| arg == "events");
+    if events {
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        let arg = match arg.into_string() {
+            Ok(arg) => arg,
+            Err(arg) => return Err(Error(format!("invalid argument {:?}", arg))),
+        };
+        // --option=value
+        let (option, inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => {
+                (arg[..i].to_string(), Some(arg[i + 1..].to_string()))
+            }
+            _ => (arg, None),
+        };
+        let mut value = |option: &str| match inline
+            .clone()
+            .or_else(|| args.next().and_then(|value| value.into_string().ok()))
+        {
+            Some(value) => Ok(value),
+            None => Err(Error(format!("{} requires a value", option))),
+        };
+        match option.as_str() {
+            "--" => {
+                parsed.command = args.by_ref().collect();
+                break;
+            }
+            "-w" | "--watch" => parsed.watch.push(value(&option)?),
+            "-c" | "--clear" => parsed.clear = true,
+            "-r" | "--restart" => parsed.restart = true,
+            "-s" | "--signal" => {
+                let signal = value(&option)?;
+                parsed.signal = parse_signal(&signal)
+                    .ok_or_else(|| Error(format!("unknown signal '{}'", signal)))?;
+            }
+            "-p" | "--postpone" => parsed.postpone = true,
+            "-d" | "--debounce" => parsed.debounce = millis(&option, &value(&option)?)?,
+            "--poll" => parsed.poll = Some(millis(&option, &value(&option)?)?),
+            "--size" if events => parsed.size = true,
+            "--hash" if events => parsed.hash = true,
+            "--metadata" if events => parsed.metadata = true,
+            "-h" | "--help" => return Ok(Parsed::Help),
+            "-V" | "--version" => return Ok(Parsed::Version),
+            _ => return Err(Error(format!("unexpected argument '{}'", option))),
+        }
+    }
+    if parsed.watch.is_empty() {
+        return Err(Error("nothing to watch, use -w <PATH|GLOB>".to_string()));
+    }
+    if events {
+        if !parsed.command.is_empty() {
+            return Err(Error("events doesn't run a command".to_string()));
+        }
+        return Ok(Parsed::Events(parsed));
+    }
+    if parsed.command.is_empty() {
+        return Err(Error("no command given, append -- <COMMAND>".to_string()));
+    }
+    Ok(Parsed::Run(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(args: &str) -> Result<Parsed, Error> {
+        parse(args.split_whitespace().map(OsString::from))
+    }
+
+    #[test]
+    fn test_parse() {
+        match parse_str(
+            "-w assets/**/*.ron --watch=Cargo.toml -r -c -s INT --poll 500 -- cargo run -q",
+        )
+        .unwrap()
+        {
+            Parsed::Run(args) => {
+                assert_eq!(args.watch, ["assets/**/*.ron", "Cargo.toml"]);
+                assert!(args.restart && args.clear && !args.postpone);
+                assert_eq!(args.signal, 2);
+                assert_eq!(args.poll, Some(Duration::from_millis(500)));
+                assert_eq!(args.command, ["cargo", "run", "-q"]);
+            }
+            _ => panic!("expected a command to run"),
+        }
+        assert_eq!(parse_str("-h").unwrap(), Parsed::Help);
+        assert!(parse_str("-w a.ron").is_err());
+        assert!(parse_str("-w a.ron -s NOPE -- true").is_err());
+        assert_eq!(parse_signal("sigkill"), Some(9));
+
+        match parse_str("events -w assets --hash").unwrap() {
+            Parsed::Events(args) => assert!(args.hash && !args.size),
+            _ => panic!("expected the events subcommand"),
+        }
+        assert!(parse_str("-w assets --hash -- true").is_err());
+        assert!(parse_str("events -w assets -- true").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+use crate::fnv::{fnv1a, FNV_OFFSET};
+use quadoculars::Fstate;
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// One line of the event stream.
+pub struct Event<'a> {
+    pub path: &'a Path,
+    pub kind: &'static str,
+    pub timestamp: u128,
+    pub size: Option<u64>,
+    pub hash: Option<u64>,
+}
+
+impl<'a> Event<'a> {
+    /// Describe the state, reading the file if its size or hash is asked for (skipped once it's gone).
+    pub fn new(state: &'a Fstate<std::path::PathBuf>, size: bool, hash: bool) -> Event<'a> {
+        let (path, kind) = match state {
+            Fstate::Changed(path) => (path, "changed"),
+            Fstate::Removed(path) => (path, "removed"),
+            Fstate::Created(path) => (path, "created"),
+            Fstate::NotFound(path) => (path, "not_found"),
+            Fstate::MetadataChanged { path, .. } => (path, "metadata_changed"),
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis());
+        let mut event = Self {
+            path,
+            kind,
+            timestamp,
+            size: None,
+            hash: None,
+        };
+        if (size || hash) && matches!(state, Fstate::Changed(_) | Fstate::Created(_)) {
+            if let Ok((len, digest)) = digest(path) {
+                event.size = if size { Some(len) } else { None };
+                event.hash = if hash { Some(digest) } else { None };
+            }
+        }
+        event
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut line = String::from("{\"path\": ");
+        escape(&self.path.to_string_lossy(), &mut line);
+        let _ = write!(
+            line,
+            ", \"kind\": \"{}\", \"timestamp\": {}",
+            self.kind, self.timestamp
+        );
+        if let Some(size) = self.size {
+            let _ = write!(line, ", \"size\": {}", size);
+        }
+        if let Some(hash) = self.hash {
+            let _ = write!(line, ", \"hash\": \"{:016x}\"", hash);
+        }
+        line.push('}');
+        line
+    }
+}
+
+/// Size and FNV-1a hash of the file content.
+fn digest(path: &Path) -> io::Result<(u64, u64)> {
+    let mut file = File::open(path)?;
+    let mut buf = [0u8; 8192];
+    let (mut len, mut hash) = (0u64, FNV_OFFSET);
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok((len, hash));
+        }
+        len += n as u64;
+        hash = fnv1a(hash, &buf[..n]);
+    }
+}
+
+/// Append the string as a JSON string literal.
+fn escape(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json() {
+        let event = Event {
+            path: Path::new("dir/\"odd\"\tname.ron"),
+            kind: "changed",
+            timestamp: 1_700_000_000_000,
+            size: Some(3),
+            hash: Some(0xdead_beef),
+        };
+        assert_eq!(
+            event.to_json(),
+            r#"{"path": "dir/\"odd\"\tname.ron", "kind": "changed", "timestamp": 1700000000000, "size": 3, "hash": "00000000deadbeef"}"#
+        );
+
+        let path = std::env::temp_dir().join("quadoculars_test_fnv");
+        std::fs::write(&path, "a").unwrap();
+        assert_eq!(digest(&path).unwrap(), (1, 0xaf63_dc4c_8601_ec8c));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,208 @@
+//! quadoculars, run a command whenever the content of the watched files changes.
+//!
+//! unlike mtime based tools, touching or re-saving a file with the same data doesn't trigger a run.
+use cfg_if::cfg_if;
+cfg_if! {
+   if #[cfg(feature = "crossbeam_channel")] {
+       use crossbeam_channel::unbounded as channel;
+   } else if #[cfg(feature = "flume_channel")] {
+       use flume::unbounded as channel;
+   } else {
+       use std::sync::mpsc::channel;
+   }
+}
+
+mod args;
+mod events;
+#[path = "../../fnv.rs"]
+mod fnv;
+mod run;
+
+use args::{Args, Parsed};
+use events::Event;
+use quadoculars::{Backend, Fstate, Watch};
+use run::Runner;
+use std::{
+    env,
+    io::{self, Write},
+    path::PathBuf,
+    process::exit,
+    time::Duration,
+};
+
+/// How often the command is checked for exiting while no change comes in.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Expand the watched paths: globs to the matching files, directories to every file below them.
+///
+/// expanded once on start, the files are then watched with Watch::multiple_files: files created later
+/// that match a glob or land in a watched directory aren't picked up until quadoculars is restarted.
+fn expand(patterns: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let path = PathBuf::from(pattern);
+        let pattern = if path.is_dir() {
+            format!("{}/**/*", glob::Pattern::escape(pattern))
+        } else if path.is_file() {
+            files.push(path);
+            continue;
+        } else {
+            pattern.clone()
+        };
+        let paths =
+            glob::glob(&pattern).map_err(|e| format!("invalid glob '{}': {}", pattern, e))?;
+        let count = files.len();
+        files.extend(
+            paths
+                .filter_map(|path| path.ok())
+                .filter(|path| path.is_file()),
+        );
+        if files.len() == count {
+            eprintln!(
+                "[quadoculars] warning: '{}' doesn't match any file",
+                pattern
+            );
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// The watch and the files to watch, persistent so removed files are picked up again once recreated.
+fn watcher(args: &Args) -> Result<(Watch, Vec<PathBuf>), String> {
+    let files = expand(&args.watch)?;
+    if files.is_empty() {
+        return Err("no file to watch".to_string());
+    }
+    let mut watch = Watch::new()
+        .set_persistent(None)
+        .set_track_metadata(args.metadata);
+    if let Some(interval) = args.poll {
+        watch = watch.set_backend(Backend::Poll(interval));
+    }
+    eprintln!("[quadoculars] watching {} file(s)", files.len());
+    Ok((watch, files))
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let (watch, mut files) = watcher(&args)?;
+    let (tx, rx) = channel();
+    watch
+        .multiple_files(&mut files, tx)
+        .map_err(|e| e.to_string())?;
+
+    let mut runner = Runner::new(args.command, args.clear, args.signal);
+    if !args.postpone {
+        runner.start();
+    }
+    let mut queued = false;
+    loop {
+        let mut changed = false;
+        if let Ok(state) = rx.recv_timeout(TICK) {
+            changed = matches!(state, Fstate::Changed(_));
+            // editors and builds touch several files at once, act once they're done.
+            while let Ok(state) = rx.recv_timeout(args.debounce) {
+                changed |= matches!(state, Fstate::Changed(_));
+            }
+        }
+        if changed {
+            if args.restart {
+                runner.stop();
+                runner.start();
+            } else {
+                queued = true;
+            }
+        }
+        let running = runner.is_running();
+        if queued && !running {
+            queued = false;
+            runner.start();
+        }
+        if interrupted() {
+            runner.stop();
+            return Ok(());
+        }
+    }
+}
+
+/// Print every file state as a line of JSON until interrupted or stdout is closed.
+fn events(args: Args) -> Result<(), String> {
+    let (watch, mut files) = watcher(&args)?;
+    let (tx, rx) = channel();
+    watch
+        .multiple_files(&mut files, tx)
+        .map_err(|e| e.to_string())?;
+    let stdout = io::stdout();
+    while !interrupted() {
+        if let Ok(state) = rx.recv_timeout(TICK) {
+            let line = Event::new(&state, args.size, args.hash).to_json();
+            let mut stdout = stdout.lock();
+            // one line per event, flushed right away for the consumer on the other end of the pipe.
+            if writeln!(stdout, "{}", line)
+                .and_then(|_| stdout.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+cfg_if! {
+    if #[cfg(unix)] {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+        extern "C" fn on_signal(_: libc::c_int) {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+        }
+
+        /// The command runs in its own process group and doesn't see Ctrl-C, stop it on the way out.
+        fn handle_signals() {
+            let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            unsafe {
+                libc::signal(libc::SIGINT, handler);
+                libc::signal(libc::SIGTERM, handler);
+                libc::signal(libc::SIGHUP, handler);
+            }
+        }
+
+        fn interrupted() -> bool {
+            INTERRUPTED.load(Ordering::SeqCst)
+        }
+    } else {
+        fn handle_signals() {}
+
+        fn interrupted() -> bool {
+            false
+        }
+    }
+}
+
+fn main() {
+    match args::parse(env::args_os().skip(1)) {
+        Ok(Parsed::Run(args)) => {
+            handle_signals();
+            if let Err(e) = run(args) {
+                eprintln!("[quadoculars] error: {}", e);
+                exit(1);
+            }
+        }
+        Ok(Parsed::Events(args)) => {
+            handle_signals();
+            if let Err(e) = events(args) {
+                eprintln!("[quadoculars] error: {}", e);
+                exit(1);
+            }
+        }
+        Ok(Parsed::Help) => print!("{}", args::USAGE),
+        Ok(Parsed::Version) => println!("quadoculars {}", env!("CARGO_PKG_VERSION")),
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, args::USAGE);
+            exit(2);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    ffi::OsString,
+    io::{self, Write},
+    process::{Child, Command, ExitStatus},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// How long a restarted command gets to exit after the signal before it's killed.
+const GRACE: Duration = Duration::from_secs(2);
+
+/// The watched command, (re)spawned on change.
+pub struct Runner {
+    command: Vec<OsString>,
+    clear: bool,
+    signal: i32,
+    child: Option<Child>,
+}
+
+impl Runner {
+    pub fn new(command: Vec<OsString>, clear: bool, signal: i32) -> Runner {
+        Self {
+            command,
+            clear,
+            signal,
+            child: None,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.clear {
+            // clear the screen and move the cursor home.
+            print!("\x1B[2J\x1B[H");
+            let _ = io::stdout().flush();
+        }
+        let mut command = Command::new(&self.command[0]);
+        command.args(&self.command[1..]);
+        #[cfg(unix)]
+        {
+            // own process group, so the signal reaches the grandchildren too (e.g. the binary behind cargo run).
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        match command.spawn() {
+            Ok(child) => self.child = Some(child),
+            Err(e) => eprintln!("[quadoculars] failed to run {:?}: {}", self.command[0], e),
+        }
+    }
+
+    /// Whether the command is still running, reports its exit status once it's done.
+    pub fn is_running(&mut self) -> bool {
+        let status = match &mut self.child {
+            Some(child) => match child.try_wait() {
+                Ok(None) => return true,
+                Ok(Some(status)) => Some(status),
+                Err(_) => None,
+            },
+            None => return false,
+        };
+        self.child = None;
+        if let Some(status) = status {
+            report(status);
+        }
+        false
+    }
+
+    /// Signal the command and wait for it to exit, kill it if it's still running after the grace period.
+    pub fn stop(&mut self) {
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => return,
+        };
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), self.signal);
+        }
+        #[cfg(not(unix))]
+        let _ = self.signal;
+        let deadline = Instant::now() + GRACE;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(None) => sleep(Duration::from_millis(10)),
+                _ => return,
+            }
+        }
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn report(status: ExitStatus) {
+    match status.code() {
+        Some(0) => eprintln!("[quadoculars] command finished"),
+        Some(code) => eprintln!("[quadoculars] command exited with code {}", code),
+        None => eprintln!("[quadoculars] command terminated by signal"),
+    }
+}
@@ -0,0 +1,243 @@
+//! Transparent decompression of gzip and zstd files.
+use std::io::{self, Cursor, Read, Write};
+
+const GZIP: &[u8] = &[0x1f, 0x8b];
+const ZSTD: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Gzip,
+    Zstd,
+}
+
+impl Kind {
+    /// By the magic bytes only, whatever the extension: a plain file named `*.gz` is plain. None for plain (and empty) files.
+    fn detect(head: &[u8]) -> Option<Kind> {
+        if head.starts_with(GZIP) {
+            Some(Kind::Gzip)
+        } else if head.starts_with(ZSTD) {
+            Some(Kind::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// How the file is compressed, None if it's plain or can't be read.
+    #[cfg_attr(not(any(feature = "live_json", feature = "live_ron")), allow(dead_code))]
+    pub(crate) fn of(file: &mut dyn Read) -> Option<Kind> {
+        let mut head = Vec::with_capacity(ZSTD.len());
+        file.take(ZSTD.len() as u64).read_to_end(&mut head).ok()?;
+        Self::detect(&head)
+    }
+
+    /// Data compressed this way, to write it back.
+    #[cfg_attr(not(any(feature = "live_json", feature = "live_ron")), allow(dead_code))]
+    pub(crate) fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Kind::Gzip => {
+                let mut gz = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+                gz.write_all(data)?;
+                gz.finish()
+            }
+            Kind::Zstd => zstd::encode_all(data, 0),
+        }
+    }
+}
+
+enum Stream {
+    Pending(Option<Box<dyn Read + Send>>),
+    Open(Box<dyn Read + Send>),
+}
+
+/// Decompressed data of the file, detected on the first read by its magic bytes.
+///
+/// anything else (empty files included) is read as is, a broken stream (e.g. caught halfway through a write) is InvalidData.
+pub(crate) struct Decompressed {
+    stream: Stream,
+}
+
+impl Decompressed {
+    pub(crate) fn new(file: Box<dyn Read + Send>) -> Decompressed {
+        Self {
+            stream: Stream::Pending(Some(file)),
+        }
+    }
+
+    fn open(&mut self, mut file: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+        let mut head = Vec::with_capacity(ZSTD.len());
+        (&mut file).take(ZSTD.len() as u64).read_to_end(&mut head)?;
+        let kind = Kind::detect(&head);
+        let data = Cursor::new(head).chain(file);
+        match kind {
+            Some(Kind::Gzip) => Ok(Box::new(flate2::read::MultiGzDecoder::new(data))),
+            Some(Kind::Zstd) => Ok(Box::new(zstd::stream::read::Decoder::new(data)?)),
+            None => Ok(Box::new(data)),
+        }
+    }
+}
+
+impl Read for Decompressed {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Stream::Pending(file) = &mut self.stream {
+            match file.take() {
+                Some(file) => self.stream = Stream::Open(self.open(file)?),
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, "unreadable")),
+            }
+        }
+        match &mut self.stream {
+            Stream::Open(data) => match data.read(buf) {
+                // flate2/zstd report a truncated stream as UnexpectedEof.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    Err(io::Error::new(io::ErrorKind::InvalidData, e))
+                }
+                read => read,
+            },
+            Stream::Pending(_) => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+
+    fn read(data: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        Decompressed::new(Box::new(Cursor::new(data))).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_decompress() {
+        let json = br#"{"lookup": [1, 2, 3]}"#.to_vec();
+        let mut gz = GzEncoder::new(Vec::new(), Compression::best());
+        gz.write_all(&json).unwrap();
+        let gz = gz.finish().unwrap();
+        let zst = zstd::encode_all(&json[..], 3).unwrap();
+
+        assert_eq!(read(gz.clone()).unwrap(), json);
+        assert_eq!(read(zst.clone()).unwrap(), json);
+        assert_eq!(read(json.clone()).unwrap(), json);
+        assert_eq!(read(Vec::new()).unwrap(), b"");
+
+        let err = read(zst[..zst.len() / 2].to_vec()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(read(gz[..gz.len() - 4].to_vec()).is_err());
+    }
+
+    #[cfg(all(feature = "live_json", feature = "testing"))]
+    #[test]
+    fn test_compressed_migrations() {
+        use crate::{testing::VirtualFs, Migrations, Watch, TRUE};
+        use flate2::read::GzDecoder;
+        use serde_json::{json, Value};
+        use std::path::PathBuf;
+
+        #[derive(serde::Deserialize)]
+        struct Table {
+            version: u64,
+            rows: Vec<u32>,
+        }
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("tables/lookup.json.gz");
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(b"{\"entries\": [1, 2]}").unwrap();
+        fs.write(&file, gz.finish().unwrap());
+        let watch = Watch::new().set_fs(&fs).set_decompress(TRUE);
+        let migrations = Migrations::<Value>::new()
+            // 0 -> 1: entries renamed to rows.
+            .step(0, |value| {
+                if let Some(table) = value.as_object_mut() {
+                    let entries = table.remove("entries").unwrap_or_default();
+                    table.insert("rows".to_string(), entries);
+                }
+                Ok(())
+            })
+            .set_write_back(true);
+
+        let mut table = Table {
+            version: 0,
+            rows: Vec::new(),
+        };
+        watch.reinit_de_json_migrated(&mut table, &file, &migrations);
+        assert_eq!((table.version, table.rows.as_slice()), (1, &[1, 2][..]));
+        // written back gzipped, and read back without migrating again.
+        let mut written = String::new();
+        GzDecoder::new(&fs.read(&file).unwrap()[..])
+            .read_to_string(&mut written)
+            .unwrap();
+        let written: Value = serde_json::from_str(&written).unwrap();
+        assert_eq!(written, json!({"version": 1, "rows": [1, 2]}));
+        table.rows.clear();
+        watch.reinit_de_json_migrated(&mut table, &file, &migrations);
+        assert_eq!(table.rows, vec![1, 2]);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_compressed() {
+        use crate::{testing::VirtualFs, Fstate, Watch, TRUE};
+        use cfg_if::cfg_if;
+        use std::path::PathBuf;
+        cfg_if! {
+           if #[cfg(feature = "crossbeam_channel")] {
+               use crossbeam_channel::unbounded as channel;
+           } else if #[cfg(feature = "flume_channel")] {
+               use flume::unbounded as channel;
+           } else {
+               use std::sync::mpsc::channel;
+           }
+        }
+
+        fn gzip(data: &str, level: u32) -> Vec<u8> {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::new(level));
+            gz.write_all(data.as_bytes()).unwrap();
+            gz.finish().unwrap()
+        }
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("tables/lookup.json.gz");
+        let table = "{\"lookup\": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]}";
+        fs.write(&file, gzip(table, 1));
+
+        let (tx, rx) = channel();
+        let watch = Watch::new().set_fs(&fs).set_decompress(TRUE);
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        // recompressed, same uncompressed data.
+        let recompressed = gzip(table, 9);
+        assert_ne!(recompressed, gzip(table, 1));
+        fs.write(&file, recompressed);
+        assert!(rx.try_recv().is_err());
+        fs.write(&file, zstd::encode_all(table.as_bytes(), 3).unwrap());
+        assert!(rx.try_recv().is_err());
+
+        // caught halfway through a write.
+        let edited = gzip("{\"lookup\": []}", 6);
+        fs.write(&file, &edited[..edited.len() / 2]);
+        assert!(rx.try_recv().is_err());
+        fs.write(&file, &edited);
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        assert_eq!(watch.stats().suppressed, 3);
+
+        // plain text named like a gzip file is read as is.
+        let notes = PathBuf::from("tables/notes.gz");
+        fs.write(&notes, "plain");
+        let (tx, rx) = channel();
+        assert!(watch.single_file(&notes, tx).unwrap());
+        fs.wait_watchers(2);
+        fs.write(&notes, "still plain");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+
+        // off by default, the raw bytes are compared.
+        let (tx, rx) = channel();
+        assert!(Watch::new().set_fs(&fs).single_file(&file, tx).unwrap());
+        fs.wait_watchers(3);
+        fs.write(&file, gzip("{\"lookup\": []}", 1));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+    }
+}
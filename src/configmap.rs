@@ -0,0 +1,97 @@
+//! Kubernetes ConfigMap/Secret volumes.
+//!
+//! The kubelet never writes the visible files, it lays out
+//! `mount/key -> ..data/key` and `mount/..data -> ..2024_01_01_00_00_00.000000001`,
+//! writes a new timestamped directory on update and atomically renames a fresh `..data` link over the old one.
+use crate::{
+    backend::Source, env::Guard, send, stats::Watched, Fstate, Sender, Watch, BREAK_POINT, FALSE,
+};
+use notify::Result;
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// The link swapped on every update.
+pub(crate) const DATA: &str = "..data";
+
+/// Whether the directory is laid out as a ConfigMap/Secret volume.
+#[inline]
+pub(crate) fn is_volume(opts: &Watch, mount: &Path) -> bool {
+    opts.env.read_link(&mount.join(DATA)).is_some()
+}
+
+/// Content of every key of the volume, as of the current `..data`.
+fn snapshot(opts: &Watch, mount: &Path) -> Result<HashMap<PathBuf, Vec<u8>>> {
+    let env = &*opts.env;
+    let mut keys = HashMap::new();
+    for path in env.read_dir(&mount.join(DATA)) {
+        let key = match path.file_name() {
+            // kubelet bookkeeping, not a key.
+            Some(name) if name.to_string_lossy().starts_with("..") => continue,
+            Some(name) => mount.join(name),
+            None => continue,
+        };
+        if let Ok(mut file) = env.open(&key) {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            keys.insert(key, data);
+        }
+    }
+    Ok(keys)
+}
+
+/// Watch the volume until it's gone, reporting every key whose content changed with a `..data` swap.
+pub(crate) fn watch_volume(
+    mount: PathBuf,
+    tx: Sender<Fstate<PathBuf>>,
+    mut opts: Watch,
+) -> Result<()> {
+    let _guard = Guard(&*opts.env);
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("config_map", mount = %mount.display()).entered();
+    let counters = opts.counters.clone();
+    let _watched = Watched::new(&counters);
+    let env = opts.env.clone();
+    let data = mount.join(DATA);
+    // only `..data` itself matters, its chain is re-read on every swap.
+    opts.follow_symlinks = FALSE;
+    let mut source = Source::new(&data, &opts)?;
+    let duration = (opts.timeout / BREAK_POINT as u32).max(Duration::from_millis(1));
+
+    let mut target = env.read_link(&data);
+    let mut keys = snapshot(&opts, &mount)?;
+    info!(keys = keys.len(), "watching");
+    loop {
+        source.wait(duration)?;
+        let current = env.read_link(&data);
+        if current == target {
+            continue;
+        }
+        if current.is_none() {
+            info!("volume unmounted, watcher terminated");
+            send(&tx, Fstate::NotFound(mount.clone()));
+            break;
+        }
+        debug!(target = ?current, "..data swapped");
+        target = current;
+        let next = snapshot(&opts, &mount)?;
+        for (key, data) in &next {
+            match keys.get(key) {
+                Some(old) if old == data => counters.suppressed(),
+                Some(_) => {
+                    counters.changed();
+                    send(&tx, Fstate::Changed(key.clone()));
+                }
+                None => send(&tx, Fstate::Created(key.clone())),
+            }
+        }
+        for key in keys.keys().filter(|key| !next.contains_key(*key)) {
+            send(&tx, Fstate::Removed(key.clone()));
+        }
+        keys = next;
+    }
+    Ok(())
+}
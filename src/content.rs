@@ -0,0 +1,159 @@
+use crate::fnv::{fnv1a, FNV_OFFSET};
+use std::{
+    io::{self, ErrorKind, Read},
+    mem,
+};
+
+/// Read (and compare) block size.
+const CHUNK: usize = 64 * 1024;
+
+/// Last known content of a watched file: the data itself, or only its length and hash above the memory cap.
+pub(crate) enum Content {
+    Data(Vec<u8>),
+    Digest { len: u64, hash: u64 },
+}
+
+impl Content {
+    /// Read the initial content.
+    pub(crate) fn read(reader: &mut dyn Read, cap: Option<u64>) -> io::Result<Content> {
+        let mut content = Content::Data(Vec::new());
+        content.update(reader, cap)?;
+        Ok(content)
+    }
+
+    /// Length of the last known content.
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub(crate) fn len(&self) -> u64 {
+        match self {
+            Content::Data(data) => data.len() as u64,
+            Content::Digest { len, .. } => *len,
+        }
+    }
+
+    /// Stream the file in blocks and compare it against the last known content, which it replaces.
+    ///
+    /// comparing stops at the first differing block, the data is reused as the new content's buffer from there.
+    /// returns true if the content changed, an empty file is not a change (the last known content is kept).
+    pub(crate) fn update(&mut self, reader: &mut dyn Read, cap: Option<u64>) -> io::Result<bool> {
+        let cap = cap.unwrap_or(u64::MAX);
+        let (mut next, old_len, old_digest) = match self {
+            Content::Data(data) => {
+                let len = data.len() as u64;
+                (Some(mem::take(data)), len, None)
+            }
+            Content::Digest { len, hash } => (Some(Vec::new()), *len, Some((*len, *hash))),
+        };
+        // Data only: next still holds the old data, equal to the new one up to len.
+        let mut matched = old_digest.is_none();
+        let mut chunk = vec![0u8; CHUNK];
+        let (mut len, mut hash) = (0u64, FNV_OFFSET);
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.restore(next, matched, old_digest);
+                    return Err(e);
+                }
+            };
+            let block = &chunk[..n];
+            match &mut next {
+                Some(data) => {
+                    let start = len as usize;
+                    if matched && data.get(start..start + n) == Some(block) {
+                        // same block, nothing to copy.
+                    } else {
+                        matched = false;
+                        data.truncate(start);
+                        data.extend_from_slice(block);
+                    }
+                    if len + n as u64 > cap {
+                        // over the cap, keep a rolling hash only from now on.
+                        hash = fnv1a(hash, &data[..start + n]);
+                        next = None;
+                        matched = false;
+                    }
+                }
+                None => hash = fnv1a(hash, block),
+            }
+            len += n as u64;
+        }
+        if len == 0 {
+            self.restore(next, matched, old_digest);
+            return Ok(false);
+        }
+        let changed = match (&mut next, old_digest) {
+            (Some(data), None) => {
+                let unchanged = matched && len == old_len;
+                data.truncate(len as usize);
+                !unchanged
+            }
+            (None, Some(old)) => (len, hash) != old,
+            // crossed the cap one way or the other, the length differs.
+            _ => true,
+        };
+        *self = match next {
+            Some(data) => Content::Data(data),
+            None => Content::Digest { len, hash },
+        };
+        Ok(changed)
+    }
+
+    /// Put the last known content back after a failed or empty read.
+    fn restore(&mut self, next: Option<Vec<u8>>, matched: bool, old_digest: Option<(u64, u64)>) {
+        *self = match (next, old_digest) {
+            (_, Some((len, hash))) => Content::Digest { len, hash },
+            (Some(data), None) if matched => Content::Data(data),
+            // the old data was partly overwritten, forget it so the next read is reported.
+            _ => Content::Data(Vec::new()),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(content: &mut Content, data: &[u8], cap: Option<u64>) -> bool {
+        content.update(&mut &data[..], cap).unwrap()
+    }
+
+    #[test]
+    fn test_streaming_compare() {
+        let big = vec![7u8; CHUNK * 2 + 10];
+        let mut content = Content::read(&mut &big[..], None).unwrap();
+        assert!(!update(&mut content, &big, None));
+
+        let mut edited = big.clone();
+        edited[CHUNK + 1] = 8;
+        assert!(update(&mut content, &edited, None));
+        assert!(matches!(&content, Content::Data(data) if *data == edited));
+        assert!(update(&mut content, &edited[..CHUNK], None));
+        assert!(update(&mut content, &big, None));
+        assert!(matches!(&content, Content::Data(data) if *data == big));
+
+        // an empty read is not a change, the content is kept.
+        assert!(!update(&mut content, b"", None));
+        assert!(!update(&mut content, &big, None));
+    }
+
+    #[test]
+    fn test_memory_cap() {
+        let cap = Some(CHUNK as u64);
+        let big = vec![1u8; CHUNK * 3];
+        let mut content = Content::read(&mut &big[..], cap).unwrap();
+        assert!(matches!(content, Content::Digest { len, .. } if len == big.len() as u64));
+        assert!(!update(&mut content, &big, cap));
+
+        let mut edited = big.clone();
+        edited[CHUNK * 2] = 2;
+        assert!(update(&mut content, &edited, cap));
+        assert!(!update(&mut content, &edited, cap));
+
+        // back under the cap.
+        assert!(update(&mut content, b"small", cap));
+        assert!(matches!(&content, Content::Data(data) if data == b"small"));
+        assert!(update(&mut content, &big, cap));
+    }
+}
@@ -0,0 +1,271 @@
+//! Decoding stage between reading a live file and deserializing it.
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+use crate::env::Env;
+use std::path::Path;
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+use std::{
+    io::{self, Cursor, Read},
+    path::PathBuf,
+    sync::Arc,
+};
+
+/// Turns the bytes of a live json/ron file into the text to deserialize, e.g. decrypts it, see Watch::set_decoder.
+///
+/// Watch::set_decoder needs live_json or live_ron: with the encrypted feature alone, AesGcm and Age
+/// can only be called directly (encrypt, decode), no watcher reads through them.
+pub trait Decoder: Send + Sync {
+    fn decode(&self, path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, String>;
+}
+
+impl<F> Decoder for F
+where
+    F: Fn(&Path, Vec<u8>) -> Result<Vec<u8>, String> + Send + Sync,
+{
+    fn decode(&self, path: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+        self(path, bytes)
+    }
+}
+
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+/// Open the file, decoded on the first read if there's a decoder: a failed decode is a read error.
+pub(crate) fn open(
+    env: &dyn Env,
+    decoder: &Option<Arc<dyn Decoder>>,
+    path: &Path,
+) -> io::Result<Box<dyn Read + Send>> {
+    let file = env.open(path)?;
+    match decoder {
+        Some(decoder) => Ok(Box::new(Decoded {
+            path: path.to_path_buf(),
+            decoder: decoder.clone(),
+            file: Some(file),
+            decoded: Cursor::new(Vec::new()),
+        })),
+        None => Ok(file),
+    }
+}
+
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+struct Decoded {
+    path: PathBuf,
+    decoder: Arc<dyn Decoder>,
+    file: Option<Box<dyn Read + Send>>,
+    decoded: Cursor<Vec<u8>>,
+}
+
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+impl Read for Decoded {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(mut file) = self.file.take() {
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes)?;
+            match self.decoder.decode(&self.path, bytes) {
+                Ok(decoded) => self.decoded = Cursor::new(decoded),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            }
+        }
+        self.decoded.read(buf)
+    }
+}
+
+#[cfg(feature = "encrypted")]
+pub use self::encrypted::{AesGcm, Age};
+
+#[cfg(feature = "encrypted")]
+mod encrypted {
+    use super::Decoder;
+    use aes_gcm::{
+        aead::{Aead, AeadCore, KeyInit, OsRng},
+        Aes256Gcm, Nonce,
+    };
+    use age::secrecy::{ExposeSecret, SecretString};
+    use std::{io::Read, path::Path};
+
+    const NONCE: usize = 12;
+
+    /// AES-256-GCM with a 32 bytes key, files are the 12 bytes nonce followed by the ciphertext and its tag.
+    pub struct AesGcm {
+        cipher: Aes256Gcm,
+    }
+
+    impl AesGcm {
+        pub fn new(key: &[u8]) -> Result<AesGcm, String> {
+            match Aes256Gcm::new_from_slice(key) {
+                Ok(cipher) => Ok(Self { cipher }),
+                Err(_) => Err(format!("AES-256-GCM key is {} bytes, not 32", key.len())),
+            }
+        }
+
+        /// Key from a keyfile holding either the 32 raw bytes or them hex encoded.
+        pub fn from_keyfile(keyfile: &Path) -> Result<AesGcm, String> {
+            let key = std::fs::read(keyfile).map_err(|e| e.to_string())?;
+            let text = String::from_utf8_lossy(&key);
+            let hex = text.trim();
+            if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                let key: Vec<u8> = (0..32)
+                    .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap_or(0))
+                    .collect();
+                return Self::new(&key);
+            }
+            Self::new(&key)
+        }
+
+        /// Encrypt with a random nonce, in the format decode expects.
+        pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let ciphertext = self
+                .cipher
+                .encrypt(&nonce, plaintext)
+                .map_err(|e| e.to_string())?;
+            Ok([nonce.as_slice(), &ciphertext].concat())
+        }
+    }
+
+    impl Decoder for AesGcm {
+        fn decode(&self, _: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+            if bytes.len() < NONCE {
+                return Err("too short for AES-GCM".to_string());
+            }
+            let (nonce, ciphertext) = bytes.split_at(NONCE);
+            self.cipher
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                // tampered with, wrong key, or caught halfway through a write.
+                .map_err(|_| "AES-GCM authentication failed".to_string())
+        }
+    }
+
+    enum Key {
+        // identity files hold boxed identities that aren't Send, parsed again for every decode.
+        Identities(SecretString),
+        Passphrase(SecretString),
+    }
+
+    /// age (binary, not armored) decryption with identities or a passphrase.
+    pub struct Age {
+        key: Key,
+    }
+
+    impl Age {
+        /// Identities in the identity file format, e.g. `AGE-SECRET-KEY-1...` lines.
+        pub fn new(identities: &str) -> Result<Age, String> {
+            Self::with_identities(SecretString::from(identities.to_string()))
+        }
+
+        pub fn from_identity_file(identity_file: &Path) -> Result<Age, String> {
+            let identities = std::fs::read_to_string(identity_file).map_err(|e| e.to_string())?;
+            Self::with_identities(SecretString::from(identities))
+        }
+
+        fn with_identities(identities: SecretString) -> Result<Age, String> {
+            let age = Self {
+                key: Key::Identities(identities),
+            };
+            age.identities()?;
+            Ok(age)
+        }
+
+        pub fn passphrase(passphrase: &str) -> Age {
+            Self {
+                key: Key::Passphrase(SecretString::from(passphrase.to_string())),
+            }
+        }
+
+        fn identities(&self) -> Result<Vec<Box<dyn age::Identity>>, String> {
+            match &self.key {
+                Key::Identities(identities) => {
+                    age::IdentityFile::from_buffer(identities.expose_secret().as_bytes())
+                        .map_err(|e| e.to_string())?
+                        .into_identities()
+                        .map_err(|e| e.to_string())
+                }
+                Key::Passphrase(passphrase) => Ok(vec![Box::new(age::scrypt::Identity::new(
+                    passphrase.clone(),
+                ))]),
+            }
+        }
+    }
+
+    impl Decoder for Age {
+        fn decode(&self, _: &Path, bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+            let identities = self.identities()?;
+            let decryptor = age::Decryptor::new_buffered(&bytes[..]).map_err(|e| e.to_string())?;
+            let mut reader = decryptor
+                .decrypt(identities.iter().map(|identity| &**identity))
+                .map_err(|e| e.to_string())?;
+            let mut plaintext = Vec::new();
+            reader
+                .read_to_end(&mut plaintext)
+                .map_err(|e| e.to_string())?;
+            Ok(plaintext)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use age::secrecy::ExposeSecret;
+
+        #[test]
+        fn test_aes_gcm() {
+            let aes = AesGcm::new(&[7; 32]).unwrap();
+            let encrypted = aes.encrypt(b"{\"volume\": 1}").unwrap();
+            let path = Path::new("audio.json.enc");
+            assert_eq!(
+                aes.decode(path, encrypted.clone()).unwrap(),
+                b"{\"volume\": 1}"
+            );
+
+            let mut tampered = encrypted;
+            *tampered.last_mut().unwrap() ^= 1;
+            assert!(aes.decode(path, tampered).is_err());
+            assert!(AesGcm::new(&[7; 16]).is_err());
+        }
+
+        #[test]
+        fn test_age() {
+            let identity = age::x25519::Identity::generate();
+            let encrypted = age::encrypt(&identity.to_public(), b"(volume: 1)").unwrap();
+            let age = Age::new(identity.to_string().expose_secret()).unwrap();
+            let path = Path::new("audio.ron.age");
+            assert_eq!(age.decode(path, encrypted).unwrap(), b"(volume: 1)");
+
+            let other = age::x25519::Identity::generate();
+            let encrypted = age::encrypt(&other.to_public(), b"(volume: 1)").unwrap();
+            assert!(age.decode(path, encrypted).is_err());
+            assert!(Age::new("not an identity").is_err());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "live_json", feature = "testing"))]
+mod tests {
+    use crate::{testing::VirtualFs, Watch};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_decoder() {
+        let fs = VirtualFs::new();
+        let path = PathBuf::from("config/audio.json.rev");
+        // "encrypted" by reversing it.
+        let watch =
+            Watch::new()
+                .set_fs(&fs)
+                .set_decoder(|_: &std::path::Path, mut bytes: Vec<u8>| {
+                    if bytes.first() != Some(&b'}') {
+                        return Err("not reversed".to_string());
+                    }
+                    bytes.reverse();
+                    Ok(bytes)
+                });
+        let mut value = json!(null);
+
+        fs.write(&path, "}1 :\"emulov\"{");
+        watch.json_val_init(&path, &mut value);
+        assert_eq!(value, json!({"volume": 1}));
+
+        fs.write(&path, "{\"volume\": 2}");
+        watch.json_val_init(&path, &mut value);
+        assert_eq!(value, json!({"volume": 1}));
+    }
+}
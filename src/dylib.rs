@@ -0,0 +1,413 @@
+//! Hot reloading dynamic libraries (`cdylib`).
+//!
+//! The library is never loaded from where the build writes it: every version is copied to its own temp path
+//! and loaded from there, so the build can overwrite the original and the loader can't hand back a cached handle.
+use crate::{Fstate, Watch, WatchSet, FALSE, TRUE};
+use cfg_if::cfg_if;
+use libloading::Library;
+use std::{
+    error, fmt, fs, io,
+    marker::PhantomData,
+    mem,
+    os::raw::c_void,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+cfg_if! {
+   if #[cfg(feature = "crossbeam_channel")] {
+       use crossbeam_channel::{unbounded as channel, Receiver};
+   } else if #[cfg(feature = "flume_channel")] {
+       use flume::{unbounded as channel, Receiver};
+   } else {
+       use std::sync::mpsc::{channel, Receiver};
+   }
+}
+
+// tells the symbols of one LiveDylib from another's.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Why a library couldn't be (re)loaded, the previous version stays loaded.
+#[derive(Debug)]
+pub enum DylibError {
+    /// copying the library to its versioned path failed.
+    Io(io::Error),
+    /// loading the copy or resolving a registered symbol failed.
+    Load(libloading::Error),
+    /// the watcher couldn't be started.
+    Watch(notify::Error),
+}
+
+impl fmt::Display for DylibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DylibError::Io(e) => write!(f, "can't copy the library: {}", e),
+            DylibError::Load(e) => write!(f, "can't load the library: {}", e),
+            DylibError::Watch(e) => write!(f, "can't watch the library: {}", e),
+        }
+    }
+}
+
+impl error::Error for DylibError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            DylibError::Io(e) => Some(e),
+            DylibError::Load(e) => Some(e),
+            DylibError::Watch(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for DylibError {
+    fn from(e: io::Error) -> Self {
+        DylibError::Io(e)
+    }
+}
+
+impl From<libloading::Error> for DylibError {
+    fn from(e: libloading::Error) -> Self {
+        DylibError::Load(e)
+    }
+}
+
+impl From<notify::Error> for DylibError {
+    fn from(e: notify::Error) -> Self {
+        DylibError::Watch(e)
+    }
+}
+
+/// Handle of a registered symbol, re-resolved on every reload. Get its current value with LiveDylib::get or Loaded::get.
+pub struct Symbol<T> {
+    owner: usize,
+    index: usize,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Symbol<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Symbol<T> {}
+
+/// One loaded version of the library.
+pub struct Loaded {
+    library: Option<Library>,
+    copy: PathBuf,
+    version: u64,
+    owner: usize,
+    symbols: Vec<*mut c_void>,
+}
+
+// the resolved symbols point into the library, which is owned alongside them.
+unsafe impl Send for Loaded {}
+unsafe impl Sync for Loaded {}
+
+impl Loaded {
+    /// Copy the library to its versioned path, load the copy and resolve every symbol.
+    fn load(
+        path: &Path,
+        owner: usize,
+        version: u64,
+        names: &[Vec<u8>],
+    ) -> Result<Loaded, DylibError> {
+        let copy = versioned(path, owner, version);
+        fs::copy(path, &copy)?;
+        let library = match unsafe { Library::new(&copy) } {
+            Ok(library) => library,
+            Err(e) => {
+                let _ = fs::remove_file(&copy);
+                return Err(e.into());
+            }
+        };
+        let mut loaded = Loaded {
+            library: Some(library),
+            copy,
+            version,
+            owner,
+            symbols: Vec::with_capacity(names.len()),
+        };
+        for name in names {
+            let symbol = loaded.resolve(name)?;
+            loaded.symbols.push(symbol);
+        }
+        Ok(loaded)
+    }
+
+    fn resolve(&self, name: &[u8]) -> Result<*mut c_void, DylibError> {
+        match &self.library {
+            Some(library) => Ok(*unsafe { library.get::<*mut c_void>(name) }?),
+            None => unreachable!("library already closed"),
+        }
+    }
+
+    /// Current value of the symbol (a fn pointer or a pointer to a static), valid as long as this version is loaded.
+    ///
+    /// The borrow ends with the Loaded, but a copy of the value doesn't: a fn pointer copied out of it, or a reference
+    /// made from the static's pointer, dangles once this version is unloaded (by the next update or reload).
+    /// Don't keep them, get the symbol again after every update.
+    ///
+    /// panics if the symbol was registered with another LiveDylib.
+    pub fn get<T>(&self, symbol: &Symbol<T>) -> &T {
+        assert_eq!(symbol.owner, self.owner, "symbol of another library");
+        // Symbol<T> is only handed out by LiveDylib::symbol, which checked T is pointer sized.
+        unsafe { &*(&self.symbols[symbol.index] as *const *mut c_void as *const T) }
+    }
+
+    /// 1 for the library loaded by Watch::live_dylib, incremented on every reload.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The versioned copy this version was loaded from.
+    pub fn path(&self) -> &Path {
+        &self.copy
+    }
+}
+
+impl Drop for Loaded {
+    fn drop(&mut self) {
+        // close the library before removing its copy.
+        drop(self.library.take());
+        let _ = fs::remove_file(&self.copy);
+    }
+}
+
+/// `dir/libgame.so` -> `$TMPDIR/libgame-<pid>-<owner>-<version>.so`
+fn versioned(path: &Path, owner: usize, version: u64) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let mut name = format!("{}-{}-{}-{}", stem, process::id(), owner, version);
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    std::env::temp_dir().join(name)
+}
+
+type Callback<S> = Box<dyn FnMut(&Loaded, &mut S) + Send>;
+
+/// A dynamic library reloaded whenever it's rebuilt, see Watch::live_dylib.
+///
+/// Reloads only happen in update (or reload), so the library in use never changes under a running call.
+/// State that must survive a reload lives in the host as `S`: on_unload callbacks get the old version to save it,
+/// on_reload callbacks get the new one to restore it. It must not keep pointers into the library (its statics, vtables...).
+pub struct LiveDylib<S = ()> {
+    path: PathBuf,
+    set: WatchSet,
+    rx: Receiver<Fstate<PathBuf>>,
+    id: usize,
+    names: Vec<Vec<u8>>,
+    loaded: Loaded,
+    state: S,
+    unload: Vec<Callback<S>>,
+    reload: Vec<Callback<S>>,
+}
+
+impl<S> LiveDylib<S> {
+    pub(crate) fn new(path: &Path, watch: &Watch, state: S) -> Result<LiveDylib<S>, DylibError> {
+        let id = NEXT_ID.fetch_add(1, Relaxed);
+        let loaded = Loaded::load(path, id, 1, &[])?;
+        let (tx, rx) = channel();
+        // a rebuild may remove the library before writing the new one.
+        let set = watch.clone().set_persistent(None).watch_set(tx);
+        set.add(path)?;
+        info!(path = %path.display(), "library loaded");
+        Ok(Self {
+            path: path.to_path_buf(),
+            set,
+            rx,
+            id,
+            names: Vec::new(),
+            loaded,
+            state,
+            unload: Vec::new(),
+            reload: Vec::new(),
+        })
+    }
+
+    /// Register the symbol, resolved now and again on every reload.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the exact type of the symbol, e.g. `extern "C" fn(i32) -> i32` for a function,
+    /// `*mut Counter` for a static, in this and every later version of the library.
+    ///
+    /// Nothing obtained from the symbol may outlive the Loaded it was read from: fn pointers copied out of get,
+    /// `&'static` references made from the static's pointer, or anything they return that points into the library.
+    /// They dangle as soon as update (or reload) unloads that version, calling or reading them is undefined behavior.
+    ///
+    /// panics if `T` isn't pointer sized.
+    pub unsafe fn symbol<T>(&mut self, name: &str) -> Result<Symbol<T>, DylibError> {
+        assert_eq!(
+            mem::size_of::<T>(),
+            mem::size_of::<*mut c_void>(),
+            "symbol type must be pointer sized"
+        );
+        let name = name.as_bytes().to_vec();
+        let symbol = self.loaded.resolve(&name)?;
+        self.loaded.symbols.push(symbol);
+        self.names.push(name);
+        Ok(Symbol {
+            owner: self.id,
+            index: self.names.len() - 1,
+            _type: PhantomData,
+        })
+    }
+
+    /// Current value of the symbol, see Loaded::get.
+    pub fn get<T>(&self, symbol: &Symbol<T>) -> &T {
+        self.loaded.get(symbol)
+    }
+
+    /// The version in use.
+    pub fn loaded(&self) -> &Loaded {
+        &self.loaded
+    }
+
+    pub fn version(&self) -> u64 {
+        self.loaded.version
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    /// Called with the old version right before it's unloaded, once the new one loaded successfully.
+    pub fn on_unload<F: FnMut(&Loaded, &mut S) + Send + 'static>(&mut self, f: F) {
+        self.unload.push(Box::new(f));
+    }
+
+    /// Called with the new version right after it's loaded and its symbols resolved.
+    pub fn on_reload<F: FnMut(&Loaded, &mut S) + Send + 'static>(&mut self, f: F) {
+        self.reload.push(Box::new(f));
+    }
+
+    /// Reload the library if it was rebuilt since the last update, true if it was reloaded. Never blocks.
+    ///
+    /// on error the previous version stays loaded and the next rebuild is tried again (e.g. the linker was still writing it).
+    pub fn update(&mut self) -> Result<bool, DylibError> {
+        let mut rebuilt = FALSE;
+        while let Ok(state) = self.rx.try_recv() {
+            match state {
+                Fstate::Changed(_) | Fstate::Created(_) => rebuilt = TRUE,
+                _ => (),
+            }
+        }
+        if rebuilt {
+            self.reload()?;
+        }
+        Ok(rebuilt)
+    }
+
+    /// Load the library again, rebuilt or not.
+    pub fn reload(&mut self) -> Result<(), DylibError> {
+        let version = self.loaded.version + 1;
+        let next = match Loaded::load(&self.path, self.id, version, &self.names) {
+            Ok(next) => next,
+            Err(e) => {
+                warn!(error = %e, "library reload failed, keeping version {}", self.loaded.version);
+                return Err(e);
+            }
+        };
+        for f in &mut self.unload {
+            f(&self.loaded, &mut self.state);
+        }
+        drop(mem::replace(&mut self.loaded, next));
+        for f in &mut self.reload {
+            f(&self.loaded, &mut self.state);
+        }
+        info!(version, "library reloaded");
+        Ok(())
+    }
+}
+
+impl<S> Drop for LiveDylib<S> {
+    fn drop(&mut self) {
+        self.set.clear();
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::{
+        thread::sleep,
+        time::{Duration, Instant},
+    };
+
+    // any shared library exporting a known symbol does, copies with trailing bytes appended stand in for rebuilds.
+    fn libm() -> Option<PathBuf> {
+        [
+            "/lib/x86_64-linux-gnu/libm.so.6",
+            "/usr/lib/x86_64-linux-gnu/libm.so.6",
+            "/lib64/libm.so.6",
+            "/usr/lib/libm.so.6",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+    }
+
+    fn updated(lib: &mut LiveDylib<Vec<u64>>) -> Result<bool, DylibError> {
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(5) {
+            match lib.update() {
+                Ok(FALSE) => sleep(Duration::from_millis(10)),
+                result => return result,
+            }
+        }
+        Ok(FALSE)
+    }
+
+    #[test]
+    fn test_live_dylib() {
+        let libm = match libm() {
+            Some(libm) => libm,
+            None => return,
+        };
+        let mut data = fs::read(&libm).unwrap();
+        let dir = crate::tests::temp_dir("live_dylib");
+        let path = dir.join("libgame.so");
+        fs::write(&path, &data).unwrap();
+
+        let watch = Watch::new().set_timeout(Duration::from_millis(140));
+        let mut lib = watch.live_dylib(&path, Vec::new()).unwrap();
+        let cos = unsafe { lib.symbol::<extern "C" fn(f64) -> f64>("cos") }.unwrap();
+        assert!(unsafe { lib.symbol::<extern "C" fn()>("no_such_symbol") }.is_err());
+        assert_eq!(lib.get(&cos)(0.0), 1.0);
+        let first = lib.loaded().path().to_path_buf();
+        assert!(first.is_file() && first != path);
+
+        lib.on_unload(|old, versions| versions.push(old.version()));
+        lib.on_reload(|new, versions| versions.push(new.version()));
+
+        // let the watcher start.
+        sleep(Duration::from_millis(100));
+        data.push(0);
+        fs::write(&path, &data).unwrap();
+        assert!(updated(&mut lib).unwrap());
+        assert_eq!(lib.version(), 2);
+        assert_eq!(lib.state(), &vec![1, 2]);
+        assert_eq!(lib.get(&cos)(0.0), 1.0);
+        // the old copy is unloaded and removed.
+        assert!(!first.exists());
+
+        // a broken build keeps the version in use.
+        fs::write(&path, b"not a library").unwrap();
+        assert!(updated(&mut lib).is_err());
+        assert_eq!(lib.version(), 2);
+        assert_eq!(lib.state(), &vec![1, 2]);
+        assert_eq!(lib.get(&cos)(0.0), 1.0);
+
+        let copy = lib.loaded().path().to_path_buf();
+        drop(lib);
+        assert!(!copy.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,282 @@
+use cfg_if::cfg_if;
+cfg_if! {
+   if #[cfg(feature = "crossbeam_channel")] {
+       use crossbeam_channel::{unbounded, Receiver};
+   } else if #[cfg(feature = "flume_channel")] {
+       use flume::{unbounded, Receiver};
+   } else {
+       use std::sync::mpsc::{channel, Receiver};
+   }
+}
+
+use crate::Fmeta;
+use notify::{Event, RecommendedWatcher, RecursiveMode::NonRecursive, Result, Watcher};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufReader, Read},
+    path::{Component, Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Metadata fingerprint (mtime, size), None if the file is missing.
+pub(crate) type Stamp = Option<(Option<SystemTime>, u64)>;
+
+/// Everything a watcher needs from the outside world: files, directory events and the clock.
+///
+/// OsEnv is the real thing, testing::VirtualFs swaps it for an in-memory filesystem and a manual clock.
+pub(crate) trait Env: Send + Sync {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>>;
+
+    /// Create or overwrite the file.
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()>;
+
+    fn stamp(&self, path: &Path) -> Stamp;
+
+    /// None if the file is missing.
+    fn metadata(&self, path: &Path) -> Option<Fmeta>;
+
+    #[inline]
+    fn exists(&self, path: &Path) -> bool {
+        self.stamp(path).is_some()
+    }
+
+    #[inline]
+    fn is_file(&self, path: &Path) -> bool {
+        self.exists(path)
+    }
+
+    /// Canonical form of the path, so equivalent paths resolve to the same one.
+    #[inline]
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        normalize(path)
+    }
+
+    /// Entries of the directory, empty if it can't be read.
+    fn read_dir(&self, dir: &Path) -> Vec<PathBuf>;
+
+    /// Target of the symlink, None if the path isn't one.
+    #[inline]
+    fn read_link(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    fn now(&self) -> Instant;
+
+    fn sleep_until(&self, deadline: Instant);
+
+    /// Subscribe to the events of a directory (non recursive).
+    fn subscribe(&self, dir: &Path) -> Result<Box<dyn Subscription>>;
+
+    /// A watcher thread is about to be spawned (called from the spawning thread).
+    fn enter(&self) {}
+
+    /// A watcher thread terminated.
+    fn leave(&self) {}
+}
+
+/// Events of a subscribed directory.
+pub(crate) trait Subscription: Send {
+    /// Wait for the next event until the deadline, None if it passed.
+    fn recv_until(&mut self, deadline: Instant) -> Option<Result<Event>>;
+
+    /// Also receive the events of another directory.
+    fn watch(&mut self, dir: &Path) -> Result<()>;
+
+    fn unwatch(&mut self, dir: &Path);
+}
+
+/// Lexically resolve `.` and `..` components.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => (),
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Deadlines past this are as good as never.
+const FOREVER: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+/// now + duration without overflowing, a duration too long for an Instant waits "forever".
+pub(crate) fn deadline(now: Instant, duration: Duration) -> Instant {
+    now.checked_add(duration)
+        .or_else(|| now.checked_add(FOREVER))
+        .unwrap_or(now)
+}
+
+/// Balances Env::enter with Env::leave when the watcher thread terminates, even by panicking.
+pub(crate) struct Guard<'a>(pub(crate) &'a dyn Env);
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        self.0.leave();
+    }
+}
+
+/// The real filesystem, native notifications and wall-clock time.
+pub(crate) struct OsEnv;
+
+impl Env for OsEnv {
+    #[inline]
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+    #[inline]
+    fn stamp(&self, path: &Path) -> Stamp {
+        fs::metadata(path)
+            .ok()
+            .map(|meta| (meta.modified().ok(), meta.len()))
+    }
+
+    fn metadata(&self, path: &Path) -> Option<Fmeta> {
+        let meta = fs::metadata(path).ok()?;
+        cfg_if! {
+            if #[cfg(unix)] {
+                use std::os::unix::fs::MetadataExt;
+                let (mode, uid, gid) = (Some(meta.mode() & 0o7777), Some(meta.uid()), Some(meta.gid()));
+            } else {
+                let (mode, uid, gid) = (None, None, None);
+            }
+        }
+        Some(Fmeta {
+            mode,
+            uid,
+            gid,
+            readonly: meta.permissions().readonly(),
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+    }
+
+    #[inline]
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    #[inline]
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    #[inline]
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        fs::write(path, data)
+    }
+
+    fn read_dir(&self, dir: &Path) -> Vec<PathBuf> {
+        match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    #[inline]
+    fn read_link(&self, path: &Path) -> Option<PathBuf> {
+        match fs::symlink_metadata(path) {
+            Ok(meta) if meta.file_type().is_symlink() => fs::read_link(path).ok(),
+            _ => None,
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> PathBuf {
+        if let Ok(path) = fs::canonicalize(path) {
+            return path;
+        }
+        // gone already, resolve its directory instead.
+        let path = match env::current_dir() {
+            Ok(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        };
+        match (path.parent(), path.file_name()) {
+            (Some(dir), Some(name)) => match fs::canonicalize(dir) {
+                Ok(dir) => dir.join(name),
+                Err(_) => normalize(&path),
+            },
+            _ => normalize(&path),
+        }
+    }
+
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn sleep_until(&self, deadline: Instant) {
+        sleep(deadline.saturating_duration_since(Instant::now()));
+    }
+
+    fn subscribe(&self, dir: &Path) -> Result<Box<dyn Subscription>> {
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx1, rx1) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx1, rx1) = unbounded();
+            }  else {
+               let (tx1, rx1) = channel();
+            }
+        }
+        let mut watcher: RecommendedWatcher = Watcher::new_immediate(move |result| {
+            let _ = tx1.send(result);
+        })?;
+        watcher.watch(dir, NonRecursive)?;
+        Ok(Box::new(Native {
+            dirs: vec![dir.to_path_buf()],
+            watcher,
+            rx1,
+        }))
+    }
+}
+
+struct Native {
+    dirs: Vec<PathBuf>,
+    watcher: RecommendedWatcher,
+    rx1: Receiver<Result<Event>>,
+}
+
+impl Subscription for Native {
+    #[inline]
+    fn recv_until(&mut self, deadline: Instant) -> Option<Result<Event>> {
+        self.rx1
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            .ok()
+    }
+
+    fn watch(&mut self, dir: &Path) -> Result<()> {
+        self.watcher.watch(dir, NonRecursive)?;
+        self.dirs.push(dir.to_path_buf());
+        Ok(())
+    }
+
+    fn unwatch(&mut self, dir: &Path) {
+        if let Some(i) = self.dirs.iter().position(|watched| watched == dir) {
+            let _ = self.watcher.unwatch(self.dirs.remove(i));
+        }
+    }
+}
+
+impl Drop for Native {
+    fn drop(&mut self) {
+        for dir in &self.dirs {
+            let _ = self.watcher.unwatch(dir);
+        }
+    }
+}
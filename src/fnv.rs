@@ -0,0 +1,16 @@
+//! 64-bit FNV-1a, stable across platforms and releases (unlike std's DefaultHasher).
+//!
+//! shared by the content digests and the cli (`events --hash`), which includes this file as its own module.
+
+/// Hash of no data, where every hash starts.
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continue the hash with the bytes.
+#[inline]
+pub(crate) fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash = (hash ^ *byte as u64).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
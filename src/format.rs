@@ -0,0 +1,190 @@
+//! Dynamic values of the live reloaded formats.
+
+/// A parsed json/ron document, serde_json::Value (live_json) and ron::Value (live_ron).
+pub trait Format: Sized + Clone + PartialEq + Send + Sync + 'static {
+    fn parse(bytes: &[u8]) -> Result<Self, String>;
+
+    fn to_bytes(&self) -> Result<Vec<u8>, String>;
+
+    fn deserialize<T: serde::de::DeserializeOwned>(self) -> Result<T, String>;
+
+    /// The value at the dot separated path of keys (or sequence indexes), e.g. `audio.volume` or `levels.0`.
+    fn select(&self, path: &str) -> Option<&Self>;
+
+    fn select_mut(&mut self, path: &str) -> Option<&mut Self>;
+
+    /// Entries of a map (with their key if it's a string) and items of a sequence.
+    fn children_mut(&mut self) -> Vec<(Option<&str>, &mut Self)>;
+
+    /// Text of a scalar (a string unquoted), None for maps and sequences.
+    fn text(&self) -> Option<String>;
+
+    /// A string value.
+    fn from_text(text: &str) -> Self;
+
+    /// Same data, whatever the order of map entries (formatting and comments are gone once parsed).
+    fn same(&self, other: &Self) -> bool;
+}
+
+#[cfg(feature = "live_json")]
+impl Format for serde_json::Value {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| e.to_string())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        serde_json::to_vec_pretty(self).map_err(|e| e.to_string())
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(self) -> Result<T, String> {
+        serde_json::from_value(self).map_err(|e| e.to_string())
+    }
+
+    fn select(&self, path: &str) -> Option<&Self> {
+        let mut value = self;
+        for key in path.split('.').filter(|key| !key.is_empty()) {
+            value = match value {
+                serde_json::Value::Array(seq) => seq.get(key.parse::<usize>().ok()?)?,
+                _ => value.get(key)?,
+            };
+        }
+        Some(value)
+    }
+
+    fn select_mut(&mut self, path: &str) -> Option<&mut Self> {
+        let mut value = self;
+        for key in path.split('.').filter(|key| !key.is_empty()) {
+            value = match value {
+                serde_json::Value::Array(seq) => seq.get_mut(key.parse::<usize>().ok()?)?,
+                _ => value.get_mut(key)?,
+            };
+        }
+        Some(value)
+    }
+
+    fn children_mut(&mut self) -> Vec<(Option<&str>, &mut Self)> {
+        match self {
+            serde_json::Value::Object(map) => map
+                .iter_mut()
+                .map(|(key, value)| (Some(key.as_str()), value))
+                .collect(),
+            serde_json::Value::Array(seq) => seq.iter_mut().map(|value| (None, value)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn text(&self) -> Option<String> {
+        match self {
+            serde_json::Value::String(text) => Some(text.clone()),
+            serde_json::Value::Object(_) | serde_json::Value::Array(_) => None,
+            scalar => Some(scalar.to_string()),
+        }
+    }
+
+    fn from_text(text: &str) -> Self {
+        serde_json::Value::String(text.to_string())
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        // maps compare as maps, with or without preserve_order.
+        self == other
+    }
+}
+
+#[cfg(feature = "live_ron")]
+impl Format for ron::Value {
+    fn parse(bytes: &[u8]) -> Result<Self, String> {
+        ron::de::from_bytes(bytes).map_err(|e| e.to_string())
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        match ron::ser::to_string_pretty(self, Default::default()) {
+            Ok(ron) => Ok(ron.into_bytes()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn deserialize<T: serde::de::DeserializeOwned>(self) -> Result<T, String> {
+        self.into_rust().map_err(|e| e.to_string())
+    }
+
+    fn select(&self, path: &str) -> Option<&Self> {
+        let mut value = self;
+        for key in path.split('.').filter(|key| !key.is_empty()) {
+            value = match value {
+                ron::Value::Map(map) => {
+                    let key = ron::Value::String(key.to_string());
+                    map.iter().find(|(k, _)| **k == key)?.1
+                }
+                ron::Value::Seq(seq) => seq.get(key.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    fn select_mut(&mut self, path: &str) -> Option<&mut Self> {
+        let mut value = self;
+        for key in path.split('.').filter(|key| !key.is_empty()) {
+            value = match value {
+                ron::Value::Map(map) => {
+                    let key = ron::Value::String(key.to_string());
+                    map.iter_mut().find(|(k, _)| **k == key)?.1
+                }
+                ron::Value::Seq(seq) => seq.get_mut(key.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    fn children_mut(&mut self) -> Vec<(Option<&str>, &mut Self)> {
+        match self {
+            ron::Value::Map(map) => map
+                .iter_mut()
+                .map(|(key, value)| match key {
+                    ron::Value::String(key) => (Some(key.as_str()), value),
+                    _ => (None, value),
+                })
+                .collect(),
+            ron::Value::Seq(seq) => seq.iter_mut().map(|value| (None, value)).collect(),
+            ron::Value::Option(Some(value)) => vec![(None, &mut **value)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn text(&self) -> Option<String> {
+        match self {
+            ron::Value::String(text) => Some(text.clone()),
+            ron::Value::Char(c) => Some(c.to_string()),
+            ron::Value::Bool(b) => Some(b.to_string()),
+            ron::Value::Number(ron::Number::Integer(n)) => Some(n.to_string()),
+            ron::Value::Number(ron::Number::Float(n)) => Some(n.get().to_string()),
+            _ => None,
+        }
+    }
+
+    fn from_text(text: &str) -> Self {
+        ron::Value::String(text.to_string())
+    }
+
+    fn same(&self, other: &Self) -> bool {
+        match (self, other) {
+            // ron::Map's own eq zips the entries in order, whatever their number.
+            (ron::Value::Map(a), ron::Value::Map(b)) => {
+                let (mut a, mut b): (Vec<_>, Vec<_>) = (a.iter().collect(), b.iter().collect());
+                a.sort_by(|x, y| x.0.cmp(y.0));
+                b.sort_by(|x, y| x.0.cmp(y.0));
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(&b)
+                        .all(|(a, b)| a.0.same(b.0) && a.1.same(b.1))
+            }
+            (ron::Value::Seq(a), ron::Value::Seq(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.same(b))
+            }
+            (ron::Value::Option(Some(a)), ron::Value::Option(Some(b))) => a.same(b),
+            _ => self == other,
+        }
+    }
+}
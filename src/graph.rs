@@ -0,0 +1,417 @@
+//! Derived values recomputed from live inputs.
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+use crate::{format::Format, redact::Redaction};
+use crate::{live::LiveFiles, Watch, FALSE, TRUE};
+use notify::Result;
+use std::{
+    any::Any,
+    collections::HashSet,
+    fmt,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+// tells the nodes of one Graph from another's.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Untyped identity of a node: its index in the graph and the graph's id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize, usize);
+
+typed_handle! {
+    /// Typed handle of a node, get its value with Graph::get (or Values::get inside a derived node).
+    pub struct Node<T>(NodeId);
+}
+
+/// State of a node.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeStatus {
+    /// the value is up to date.
+    Ready,
+    /// (inputs only) the file couldn't be read or parsed, the last good value is kept.
+    Failed(String),
+    /// (derived only) an input failed or has no value yet, the last value is kept until it recovers.
+    Blocked,
+}
+
+/// What a node is, see Graph::nodes.
+#[derive(Clone, Debug)]
+pub struct NodeInfo {
+    pub id: NodeId,
+    pub name: String,
+    /// the watched file, None for derived nodes.
+    pub path: Option<PathBuf>,
+    pub inputs: Vec<NodeId>,
+    pub status: NodeStatus,
+    /// how many values it produced, the initial one included.
+    pub computed: u64,
+}
+
+type Parse = Box<dyn Fn(&[u8]) -> std::result::Result<Box<dyn Any>, String>>;
+type Compute = Box<dyn FnMut(&Values) -> Box<dyn Any>>;
+
+enum Kind {
+    Input {
+        path: PathBuf,
+        parse: Parse,
+    },
+    Derived {
+        inputs: Vec<NodeId>,
+        compute: Compute,
+    },
+}
+
+struct Slot {
+    name: String,
+    kind: Kind,
+    value: Option<Box<dyn Any>>,
+    status: NodeStatus,
+    computed: u64,
+}
+
+/// Values of the nodes a derived node is computed from.
+pub struct Values<'a>(&'a [Slot], &'a [NodeId]);
+
+impl<'a> Values<'a> {
+    /// The current value of the node, panics if it's not one of the derived node's inputs.
+    pub fn get<T: 'static>(&self, node: &Node<T>) -> &'a T {
+        assert!(self.1.contains(&node.id), "not an input of this node");
+        self.0
+            .get(node.id.0)
+            .and_then(|slot| slot.value.as_ref())
+            .and_then(|value| value.downcast_ref())
+            .expect("not an input of this node")
+    }
+}
+
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+fn scrub<V: Format>(
+    redaction: &Option<std::sync::Arc<Redaction>>,
+    bytes: &[u8],
+    error: impl fmt::Display,
+) -> String {
+    match (redaction, V::parse(bytes)) {
+        (Some(redaction), Ok(document)) => redaction.scrub(&document, &error.to_string()),
+        _ => error.to_string(),
+    }
+}
+
+/// A graph of live inputs (watched files) and nodes derived from them, see Watch::graph.
+///
+/// Nodes can only be derived from nodes declared before them, so the order of declaration is a topological order:
+/// update recomputes every node whose inputs changed exactly once, after all of its inputs.
+pub struct Graph {
+    id: usize,
+    // inputs are read (decoded, decompressed) and redacted as this watch does.
+    watch: Watch,
+    files: LiveFiles,
+    slots: Vec<Slot>,
+}
+
+impl Graph {
+    pub(crate) fn new(watch: &Watch) -> Graph {
+        Self {
+            id: NEXT_ID.fetch_add(1, Relaxed),
+            watch: watch.clone(),
+            files: LiveFiles::new(watch),
+            slots: Vec::new(),
+        }
+    }
+
+    /// Add an input parsed from the file with parse, Err if the file doesn't exist or can't be watched.
+    ///
+    /// an input that doesn't parse is added with NodeStatus::Failed and no value, blocking the nodes derived from it.
+    pub fn input<T, E, F>(&mut self, path: &Path, parse: F) -> Result<Node<T>>
+    where
+        T: 'static,
+        E: fmt::Display,
+        F: Fn(&[u8]) -> std::result::Result<T, E> + 'static,
+    {
+        if !self.watch.env.is_file(path) {
+            return Err(notify::Error::path_not_found().add_path(path.to_path_buf()));
+        }
+        let key = self.watch.env.canonicalize(path);
+        self.files.add(&key)?;
+        let parse: Parse = Box::new(move |bytes| match parse(bytes) {
+            Ok(value) => Ok(Box::new(value)),
+            Err(e) => Err(e.to_string()),
+        });
+        self.slots.push(Slot {
+            name: path.display().to_string(),
+            kind: Kind::Input { path: key, parse },
+            value: None,
+            status: NodeStatus::Blocked,
+            computed: 0,
+        });
+        let id = self.slots.len() - 1;
+        self.parse(id);
+        Ok(Node::new(NodeId(id, self.id)))
+    }
+
+    #[cfg(feature = "live_json")]
+    /// Add an input deserialized from json.
+    pub fn json<T: serde::de::DeserializeOwned + 'static>(
+        &mut self,
+        json: &Path,
+    ) -> Result<Node<T>> {
+        let redaction = self.watch.redaction.clone();
+        self.input(json, move |bytes| {
+            serde_json::from_slice::<T>(bytes)
+                .map_err(|e| scrub::<serde_json::Value>(&redaction, bytes, e))
+        })
+    }
+
+    #[cfg(feature = "live_ron")]
+    /// Add an input deserialized from ron.
+    pub fn ron<T: serde::de::DeserializeOwned + 'static>(&mut self, ron: &Path) -> Result<Node<T>> {
+        let redaction = self.watch.redaction.clone();
+        self.input(ron, move |bytes| {
+            ron::de::from_bytes::<T>(bytes).map_err(|e| scrub::<ron::Value>(&redaction, bytes, e))
+        })
+    }
+
+    /// Add a node computed from the given nodes, computed right away if they all have a value.
+    ///
+    /// compute gets their values through Values::get.
+    /// Panics if an input isn't a node declared before in this graph.
+    pub fn derive<T, F>(&mut self, name: &str, inputs: &[NodeId], mut compute: F) -> Node<T>
+    where
+        T: 'static,
+        F: FnMut(&Values) -> T + 'static,
+    {
+        for input in inputs {
+            assert!(
+                self.contains(*input),
+                "{:?} isn't a node of this graph, declare the inputs of {} first",
+                input,
+                name
+            );
+        }
+        self.slots.push(Slot {
+            name: name.to_string(),
+            kind: Kind::Derived {
+                inputs: inputs.to_vec(),
+                compute: Box::new(move |values| Box::new(compute(values))),
+            },
+            value: None,
+            status: NodeStatus::Blocked,
+            computed: 0,
+        });
+        let id = self.slots.len() - 1;
+        self.compute(id);
+        Node::new(NodeId(id, self.id))
+    }
+
+    /// The current value of the node, the last good one if it failed or is blocked. None if it never had one.
+    pub fn get<T: 'static>(&self, node: &Node<T>) -> Option<&T> {
+        self.slot(node.id)?.value.as_ref()?.downcast_ref()
+    }
+
+    pub fn status(&self, id: NodeId) -> Option<&NodeStatus> {
+        self.slot(id).map(|slot| &slot.status)
+    }
+
+    fn contains(&self, id: NodeId) -> bool {
+        id.1 == self.id && id.0 < self.slots.len()
+    }
+
+    fn slot(&self, id: NodeId) -> Option<&Slot> {
+        self.slots.get(id.0).filter(|_| id.1 == self.id)
+    }
+
+    /// Every node in declaration (topological) order.
+    pub fn nodes(&self) -> Vec<NodeInfo> {
+        self.slots
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| {
+                let (path, inputs) = match &slot.kind {
+                    Kind::Input { path, .. } => (Some(path.clone()), Vec::new()),
+                    Kind::Derived { inputs, .. } => (None, inputs.clone()),
+                };
+                NodeInfo {
+                    id: NodeId(i, self.id),
+                    name: slot.name.clone(),
+                    path,
+                    inputs,
+                    status: slot.status.clone(),
+                    computed: slot.computed,
+                }
+            })
+            .collect()
+    }
+
+    /// Re-parse the inputs whose file changed since the last update and recompute what's derived from them, never blocks.
+    ///
+    /// returns the nodes that got a new value, in the order they were updated.
+    pub fn update(&mut self) -> Vec<NodeId> {
+        let files = self.files.changed();
+        let mut updated = Vec::new();
+        if files.is_empty() {
+            return updated;
+        }
+        // re-parsed or recomputed (successfully or not), a failure blocks everything downstream.
+        let mut touched = HashSet::new();
+        for id in 0..self.slots.len() {
+            let fresh = match &self.slots[id].kind {
+                Kind::Input { path, .. } if files.contains(path) => self.parse(id),
+                Kind::Derived { inputs, .. }
+                    if inputs.iter().any(|input| touched.contains(input)) =>
+                {
+                    self.compute(id)
+                }
+                _ => continue,
+            };
+            touched.insert(NodeId(id, self.id));
+            if fresh {
+                updated.push(NodeId(id, self.id));
+            }
+        }
+        updated
+    }
+
+    fn parse(&mut self, id: usize) -> bool {
+        let slot = &mut self.slots[id];
+        let (path, parse) = match &slot.kind {
+            Kind::Input { path, parse } => (path, parse),
+            Kind::Derived { .. } => return FALSE,
+        };
+        let mut bytes = Vec::new();
+        let parsed = match self
+            .watch
+            .open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+        {
+            Ok(_) => parse(&bytes),
+            Err(e) => Err(e.to_string()),
+        };
+        match parsed {
+            Ok(value) => {
+                slot.value = Some(value);
+                slot.status = NodeStatus::Ready;
+                slot.computed += 1;
+                TRUE
+            }
+            Err(error) => {
+                warn!(path = %path.display(), error = %error, "input failed to parse, downstream nodes blocked");
+                slot.status = NodeStatus::Failed(error);
+                FALSE
+            }
+        }
+    }
+
+    fn compute(&mut self, id: usize) -> bool {
+        let (before, rest) = self.slots.split_at_mut(id);
+        let slot = &mut rest[0];
+        let (inputs, compute) = match &mut slot.kind {
+            Kind::Derived { inputs, compute } => (inputs, compute),
+            Kind::Input { .. } => return FALSE,
+        };
+        let ready = inputs.iter().all(|input| {
+            before
+                .get(input.0)
+                .is_some_and(|input| input.status == NodeStatus::Ready && input.value.is_some())
+        });
+        if !ready {
+            debug!(node = %slot.name, "blocked by an input");
+            slot.status = NodeStatus::Blocked;
+            return FALSE;
+        }
+        slot.value = Some(compute(&Values(before, inputs)));
+        slot.status = NodeStatus::Ready;
+        slot.computed += 1;
+        TRUE
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualFs;
+    use std::time::Duration;
+
+    fn number(bytes: &[u8]) -> std::result::Result<i64, String> {
+        let text = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+        text.trim()
+            .parse()
+            .map_err(|e: std::num::ParseIntError| e.to_string())
+    }
+
+    #[test]
+    fn test_graph() {
+        let fs = VirtualFs::new();
+        let (a_file, b_file) = (PathBuf::from("inputs/a"), PathBuf::from("inputs/b"));
+        fs.write(&a_file, "1");
+        fs.write(&b_file, "2");
+
+        let mut graph = Watch::new().set_fs(&fs).graph();
+        let a = graph.input(&a_file, number).unwrap();
+        let b = graph.input(&b_file, number).unwrap();
+        let sum = graph.derive("sum", &[a.id(), b.id()], move |v| v.get(&a) + v.get(&b));
+        let double = graph.derive("double", &[sum.id()], move |v| v.get(&sum) * 2);
+        let plain = graph.derive("plain", &[b.id()], move |v| *v.get(&b));
+        assert_eq!(graph.get(&double), Some(&6));
+        assert!(graph.input(Path::new("inputs/missing"), number).is_err());
+        fs.wait_watchers(2);
+        assert!(graph.update().is_empty());
+
+        fs.write(&a_file, "5");
+        assert_eq!(graph.update(), vec![a.id(), sum.id(), double.id()]);
+        assert_eq!(graph.get(&double), Some(&14));
+        let computed: Vec<u64> = graph.nodes().iter().map(|node| node.computed).collect();
+        assert_eq!(computed, vec![2, 1, 2, 2, 1]);
+
+        // a broken input blocks everything downstream, keeping the last values.
+        fs.write(&b_file, "two");
+        assert!(graph.update().is_empty());
+        assert!(matches!(graph.status(b.id()), Some(NodeStatus::Failed(_))));
+        for node in [sum.id(), double.id(), plain.id()] {
+            assert_eq!(graph.status(node), Some(&NodeStatus::Blocked));
+        }
+        assert_eq!(graph.get(&double), Some(&14));
+
+        fs.write(&b_file, "3");
+        assert_eq!(
+            graph.update(),
+            vec![b.id(), sum.id(), double.id(), plain.id()]
+        );
+        assert_eq!(graph.get(&double), Some(&16));
+        assert!(graph
+            .nodes()
+            .iter()
+            .all(|node| node.status == NodeStatus::Ready));
+
+        // the watchers stop with the graph.
+        drop(graph);
+        fs.advance(Duration::from_secs(1));
+        assert_eq!(fs.watchers(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't a node of this graph")]
+    fn test_derive_foreign_input() {
+        let fs = VirtualFs::new();
+        fs.write("inputs/a", "1");
+        let mut other = Watch::new().set_fs(&fs).graph();
+        let a = other.input(Path::new("inputs/a"), number).unwrap();
+
+        // same index, another graph.
+        let mut graph = Watch::new().set_fs(&fs).graph();
+        graph.input(Path::new("inputs/a"), number).unwrap();
+        assert_eq!(graph.get(&a), None);
+        graph.derive("double", &[a.id()], move |v| v.get(&a) * 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "not an input of this node")]
+    fn test_values_of_inputs_only() {
+        let fs = VirtualFs::new();
+        fs.write("inputs/a", "1");
+        fs.write("inputs/b", "2");
+        let mut graph = Watch::new().set_fs(&fs).graph();
+        let a = graph.input(Path::new("inputs/a"), number).unwrap();
+        let b = graph.input(Path::new("inputs/b"), number).unwrap();
+        graph.derive("sum", &[a.id()], move |v| v.get(&a) + v.get(&b));
+    }
+}
@@ -1,11 +1,90 @@
 use cfg_if::cfg_if;
+
+#[macro_use]
+mod trace;
+#[cfg(any(
+    feature = "assets",
+    feature = "graph",
+    feature = "live_json",
+    feature = "live_ron"
+))]
+#[macro_use]
+mod live;
+#[cfg(feature = "assets")]
+mod assets;
+mod backend;
+#[cfg(feature = "compressed")]
+mod compress;
+mod configmap;
+mod content;
+#[cfg(any(feature = "live_json", feature = "live_ron", feature = "encrypted"))]
+mod decode;
+#[cfg(feature = "live_dylib")]
+mod dylib;
+mod env;
+mod fnv;
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+mod format;
+#[cfg(feature = "graph")]
+mod graph;
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+mod migrate;
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+mod redact;
+mod retry;
+#[cfg(feature = "json_schema")]
+mod schema;
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+mod sections;
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+mod semantic;
+mod set;
+mod stats;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "assets")]
+pub use assets::{AssetEvent, AssetId, AssetServer, Handle, Loader};
+pub use backend::Backend;
+use backend::Source;
+use content::Content;
+#[cfg(any(feature = "live_json", feature = "live_ron", feature = "encrypted"))]
+pub use decode::Decoder;
+#[cfg(feature = "encrypted")]
+pub use decode::{AesGcm, Age};
+#[cfg(feature = "live_dylib")]
+pub use dylib::{DylibError, LiveDylib, Loaded, Symbol};
+use env::{Env, Guard, OsEnv};
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+pub use format::Format;
+#[cfg(feature = "graph")]
+pub use graph::{Graph, Node, NodeId, NodeInfo, NodeStatus, Values};
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+pub use migrate::{Migrations, Versioned};
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+pub use redact::{Redaction, REDACTED};
+pub use retry::{Backoff, RetryPolicy};
+#[cfg(feature = "json_schema")]
+pub use schema::{Schema, SchemaViolation};
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+pub use sections::{Section, SectionId, Sections};
+#[cfg(any(feature = "live_json", feature = "live_ron"))]
+use semantic::Semantic;
+use set::Stopped;
+pub use set::WatchSet;
+pub use stats::Stats;
+use stats::{Counters, Watched};
+
 cfg_if! {
    if #[cfg(feature = "crossbeam_channel")] {
-       use crossbeam_channel::{bounded, Sender};
+       use crossbeam_channel::Sender;
+       #[cfg(any(feature = "live_json", feature = "live_ron"))]
+       use crossbeam_channel::bounded;
    } else if #[cfg(feature = "flume_channel")] {
-       use flume::{bounded, Sender};
+       use flume::Sender;
+       #[cfg(any(feature = "live_json", feature = "live_ron"))]
+       use flume::bounded;
    } else {
-       use std::sync::mpsc::{sync_channel, Sender};
+       use std::sync::mpsc::Sender;
    }
 }
 
@@ -15,21 +94,19 @@ cfg_if! {
     }
 }
 
-use notify::{
-    event::ModifyKind, EventKind, RecommendedWatcher, RecursiveMode::NonRecursive, Result, Watcher,
-};
+use notify::Result;
 use std::{
     cmp::Ordering,
-    fs::{File, OpenOptions},
-    io::{BufReader, Read},
-    path::PathBuf,
-    thread::{sleep, spawn},
-    time::Duration,
-    usize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc,
+    },
+    thread::spawn,
+    time::{Duration, Instant, SystemTime},
 };
 
 const ZERO: usize = 0;
-const MILLIS: f32 = 1000.0;
 const BREAK_POINT: usize = 7;
 const TRUE: bool = true;
 const FALSE: bool = false;
@@ -39,142 +116,533 @@ const FALSE: bool = false;
 pub enum Fstate<T> {
     Changed(T),
     NotFound(T),
+    /// (persistent mode only) the file is gone for longer than the timeout, the watcher keeps waiting for it.
+    /// (config_map) the key was dropped from the volume.
+    Removed(T),
+    /// (persistent mode only) the file is back after being removed, followed by Changed if the data differs.
+    /// (config_map) the key was added to the volume.
+    Created(T),
+    /// (set_track_metadata only) permissions, ownership, mtime or size changed.
+    MetadataChanged {
+        path: T,
+        old: Fmeta,
+        new: Fmeta,
+    },
 }
 
-#[inline]
-fn watch(file: PathBuf, tx: Sender<Fstate<PathBuf>>, timeout: f32) -> Result<()> {
-    cfg_if! {
-        if  #[cfg(feature = "crossbeam_channel")] {
-           let (tx1, rx1) = bounded(ZERO);
-        } else if #[cfg(feature = "flume_channel")] {
-           let (tx1, rx1) = bounded(ZERO);
-        }  else {
-           let (tx1, rx1) = sync_channel(ZERO);
+/// File metadata tracked with Watch::set_track_metadata.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fmeta {
+    /// Permission bits (unix only).
+    pub mode: Option<u32>,
+    /// Owner (unix only).
+    pub uid: Option<u32>,
+    /// Group (unix only).
+    pub gid: Option<u32>,
+    /// No write permission.
+    pub readonly: bool,
+    /// Last modification time, None where the platform doesn't report it.
+    pub modified: Option<SystemTime>,
+    /// Size in bytes.
+    pub len: u64,
+}
+
+impl<T> Fstate<T> {
+    #[cfg(feature = "tracing")]
+    fn kind(&self) -> &'static str {
+        match self {
+            Fstate::Changed(_) => "changed",
+            Fstate::NotFound(_) => "not_found",
+            Fstate::Removed(_) => "removed",
+            Fstate::Created(_) => "created",
+            Fstate::MetadataChanged { .. } => "metadata_changed",
         }
     }
+}
 
-    let mut watcher: RecommendedWatcher = Watcher::new_immediate(move |result| {
-        let _ = tx1.try_send(result);
-    })?;
-    watcher.watch(&file, NonRecursive)?;
+/// Send the state, the watcher carries on (or terminates) whether or not anyone is still listening.
+#[inline]
+fn send<T>(tx: &Sender<Fstate<T>>, state: Fstate<T>) {
+    debug!(state = state.kind(), "emit");
+    if tx.send(state).is_err() {
+        debug!("receiver dropped, state discarded");
+    }
+}
 
-    let mut first_data;
-    {
-        match OpenOptions::new().read(TRUE).open(&file) {
-            Ok(tmp_file) => {
-                let mut buf = Vec::new();
-                BufReader::new(tmp_file).read_to_end(&mut buf)?;
-                first_data = buf;
-            }
-            Err(e) => {
-                panic!("{} {}", e, file.to_string_lossy());
+/// Open the data of the file, decompressed if it's gzip or zstd (`compressed` feature).
+#[inline]
+#[cfg_attr(not(feature = "compressed"), allow(unused_variables))]
+fn open_data(file: &Path, opts: &Watch) -> std::io::Result<Box<dyn std::io::Read + Send>> {
+    let data = opts.env.open(file)?;
+    #[cfg(feature = "compressed")]
+    let data: Box<dyn std::io::Read + Send> = match opts.decompress {
+        TRUE => Box::new(compress::Decompressed::new(data)),
+        FALSE => data,
+    };
+    Ok(data)
+}
+
+/// What a watcher knows of its file: its content and, comparing semantically, its parsed value.
+struct Last {
+    content: Content,
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    semantic: Option<Semantic>,
+}
+
+/// Read the file and compare it against the last known content, returns None if the file can't be opened.
+#[inline]
+fn read_changed(
+    counters: &Counters,
+    file: &Path,
+    last: &mut Last,
+    opts: &Watch,
+) -> Result<Option<bool>> {
+    match open_data(file, opts) {
+        Ok(mut tmp_file) => {
+            let started = Instant::now();
+            let changed = match last.content.update(&mut *tmp_file, opts.memory_cap) {
+                Ok(changed) => changed,
+                // a compressed file caught halfway through a write, read again on its next change.
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                    debug!(error = %e, "content can't be decompressed yet");
+                    FALSE
+                }
+                Err(e) => return Err(e.into()),
+            };
+            #[cfg(any(feature = "live_json", feature = "live_ron"))]
+            let changed = match &mut last.semantic {
+                Some(semantic) if changed => semantic.changed(opts.open(file)),
+                _ => changed,
+            };
+            counters.read(started.elapsed());
+            if changed {
+                debug!(len = last.content.len(), "content changed");
+            } else {
+                debug!("content unchanged, suppressed");
             }
+            Ok(Some(changed))
+        }
+        Err(_) => {
+            trace!("can't open the file");
+            Ok(None)
         }
     }
+}
+
+/// Watch the file until it's gone (or stopped), reporting its states with the tag.
+#[inline]
+fn watch<T: Clone>(file: PathBuf, tag: T, tx: Sender<Fstate<T>>, opts: Watch) -> Result<()> {
+    let _guard = Guard(&*opts.env);
+    // dropped before the Guard: the WatchSet learns the watcher is gone before the Env does.
+    let _stopped = Stopped(opts.stop.as_deref());
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("watcher", path = %file.display()).entered();
+    let result = watching(file, tag, tx, &opts);
+    #[cfg(feature = "tracing")]
+    if let Err(e) = &result {
+        error!(error = %e, "watcher failed");
+    }
+    result
+}
+
+#[inline]
+fn watching<T: Clone>(file: PathBuf, tag: T, tx: Sender<Fstate<T>>, opts: &Watch) -> Result<()> {
+    let env = &*opts.env;
+    let counters = &*opts.counters;
+    let _watched = Watched::new(counters);
+    let mut source = Source::new(&file, opts)?;
+
+    let content = match open_data(&file, opts) {
+        Ok(mut tmp_file) => match Content::read(&mut *tmp_file, opts.memory_cap) {
+            Ok(content) => content,
+            // unknown until it decompresses, its first good read is reported.
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => Content::Data(Vec::new()),
+            Err(e) => return Err(e.into()),
+        },
+        Err(e) => {
+            error!(error = %e, "can't read the file to watch");
+            panic!("{} {}", e, file.to_string_lossy());
+        }
+    };
+    let mut last = Last {
+        content,
+        #[cfg(any(feature = "live_json", feature = "live_ron"))]
+        semantic: Semantic::new(&file, opts),
+    };
 
     let duration: Duration;
     {
-        let timeout = (timeout * MILLIS) as u32;
-        duration = Duration::from_millis((timeout / BREAK_POINT as u32).into());
-    }
-
-    while let Some(_file_) = Some(file.exists()) {
-        if _file_ {
-            if let Ok(event) = rx1.recv_timeout(duration) {
-                match event?.kind {
-                    EventKind::Modify(kind) => match kind {
-                        ModifyKind::Any => match File::open(&file) {
-                            Ok(tmp_file) => {
-                                let mut seconds_data = Vec::new();
-                                BufReader::new(tmp_file).read_to_end(&mut seconds_data)?;
-                                let seconds_data_len = seconds_data.len();
-                                if seconds_data_len > ZERO {
-                                    match first_data.cmp(&seconds_data) {
-                                        Ordering::Less | Ordering::Greater => {
-                                            let _ = tx.send(Fstate::Changed(file.clone()));
-                                            first_data = seconds_data;
-                                        }
-                                        _ => drop(seconds_data),
-                                    }
-                                }
-                            }
-                            Err(_) => {
-                                let _ = tx.send(Fstate::NotFound(file.clone()));
-                                break;
-                            }
-                        },
-                        _ => (),
-                    },
-                    _ => (),
+        duration = (opts.timeout / BREAK_POINT as u32).max(Duration::from_millis(1));
+    }
+    let policy = opts.retry_policy();
+    let metadata = || match opts.track_metadata {
+        TRUE => env.metadata(&file),
+        FALSE => None,
+    };
+    let mut last_meta = metadata();
+    info!(backend = ?opts.backend, persistent = opts.persistent, "watching");
+    // removed from its WatchSet, terminate quietly.
+    let stopped = || opts.stop.as_ref().map_or(FALSE, |stop| stop.load(Relaxed));
+    let emit = |state| {
+        if !stopped() {
+            send(&tx, state);
+        }
+    };
+
+    'watching: loop {
+        if stopped() {
+            info!("watcher stopped");
+            break;
+        }
+        if env.exists(&file) {
+            if source.wait(duration)? {
+                match read_changed(counters, &file, &mut last, opts)? {
+                    Some(TRUE) => {
+                        counters.changed();
+                        emit(Fstate::Changed(tag.clone()));
+                    }
+                    Some(_) => counters.suppressed(),
+                    None => {
+                        if !opts.persistent {
+                            info!("file vanished while reading, watcher terminated");
+                            emit(Fstate::NotFound(tag.clone()));
+                            break;
+                        }
+                    }
+                }
+            }
+            if opts.track_metadata {
+                let meta = env.metadata(&file);
+                if let (Some(old), Some(new)) = (last_meta, meta) {
+                    if old != new {
+                        debug!(old = ?old, new = ?new, "metadata changed");
+                        emit(Fstate::MetadataChanged {
+                            path: tag.clone(),
+                            old,
+                            new,
+                        });
+                    }
+                }
+                if meta.is_some() {
+                    last_meta = meta;
                 }
             }
         } else {
             let mut check_point = ZERO;
-            'retry: while check_point <= BREAK_POINT {
-                sleep(duration);
-                match File::open(&file) {
-                    Ok(tmp_file) => {
-                        let mut seconds_data = Vec::new();
-                        BufReader::new(tmp_file).read_to_end(&mut seconds_data)?;
-                        let seconds_data_len = seconds_data.len();
-                        if seconds_data_len > ZERO {
-                            match first_data.cmp(&seconds_data) {
-                                Ordering::Less | Ordering::Greater => {
-                                    let _ = tx.send(Fstate::Changed(file.clone()));
-                                    first_data = seconds_data;
-                                    break;
-                                }
-                                _ => drop(seconds_data),
-                            }
+            'retry: while check_point < policy.attempts() {
+                if stopped() {
+                    continue 'watching;
+                }
+                // wake up early if the file comes back (e.g. renamed over), otherwise retry after the delay.
+                let delay = policy.delay(check_point);
+                debug!(attempt = check_point, delay = ?delay, "file missing, retrying");
+                source.wait(delay)?;
+                match read_changed(counters, &file, &mut last, opts)? {
+                    Some(changed) => {
+                        // back before the retries ran out, possibly a new file: not a metadata change.
+                        last_meta = metadata();
+                        if changed {
+                            counters.changed();
+                            emit(Fstate::Changed(tag.clone()));
                         }
+                        break;
                     }
-                    Err(_) => {
+                    None => {
                         check_point += 1;
+                        counters.retry();
                         continue 'retry;
                     }
                 }
             }
-            if check_point > BREAK_POINT {
-                let _ = tx.send(Fstate::NotFound(file.clone()));
-                break;
+            if check_point >= policy.attempts() {
+                if !opts.persistent {
+                    info!(attempts = check_point, "file not found, watcher terminated");
+                    emit(Fstate::NotFound(tag.clone()));
+                    break;
+                }
+                info!("file removed, waiting for it to be recreated");
+                emit(Fstate::Removed(tag.clone()));
+                let started = env.now();
+                // keep waiting on the parent directory until the file is recreated or max_wait is exceeded.
+                let changed = loop {
+                    if stopped() {
+                        continue 'watching;
+                    }
+                    source.wait(duration)?;
+                    if let Some(changed) = read_changed(counters, &file, &mut last, opts)? {
+                        break Some(changed);
+                    }
+                    if let Some(max_wait) = opts.max_wait {
+                        if env.now().saturating_duration_since(started) >= max_wait {
+                            break None;
+                        }
+                    }
+                };
+                match changed {
+                    Some(changed) => {
+                        info!("file recreated");
+                        emit(Fstate::Created(tag.clone()));
+                        // a new file, not a metadata change.
+                        last_meta = metadata();
+                        if changed {
+                            counters.changed();
+                            emit(Fstate::Changed(tag.clone()));
+                        }
+                    }
+                    None => {
+                        info!(max_wait = ?opts.max_wait, "file not recreated in time, watcher terminated");
+                        emit(Fstate::NotFound(tag.clone()));
+                        break;
+                    }
+                }
             }
         }
     }
-    drop(first_data);
-    watcher.unwatch(file)?;
-    drop(watcher);
+    drop(last);
+    drop(source);
     Ok(())
 }
 
-const TIMEOUT: f32 = 0.63;
+const TIMEOUT: Duration = Duration::from_millis(630);
 #[derive(Clone)]
 pub struct Watch {
-    timeout: f32,
+    env: Arc<dyn Env>,
+    timeout: Duration,
+    retry: Option<RetryPolicy>,
+    backend: Backend,
+    persistent: bool,
+    max_wait: Option<Duration>,
+    counters: Arc<Counters>,
+    follow_symlinks: bool,
+    track_metadata: bool,
+    memory_cap: Option<u64>,
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    redaction: Option<Arc<Redaction>>,
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    decoder: Option<Arc<dyn Decoder>>,
+    #[cfg(feature = "compressed")]
+    decompress: bool,
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    semantic: bool,
+    // only set on the copy handed to a watcher spawned by a WatchSet,
+    // raised by the set to stop the watcher and by the watcher once it terminated.
+    stop: Option<Arc<AtomicBool>>,
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Watch {
     pub fn new() -> Watch {
-        Self { timeout: TIMEOUT }
+        Self {
+            env: Arc::new(OsEnv),
+            timeout: TIMEOUT,
+            retry: None,
+            backend: Backend::Native,
+            persistent: FALSE,
+            max_wait: None,
+            counters: Arc::default(),
+            follow_symlinks: FALSE,
+            track_metadata: FALSE,
+            memory_cap: None,
+            #[cfg(any(feature = "live_json", feature = "live_ron"))]
+            redaction: None,
+            #[cfg(any(feature = "live_json", feature = "live_ron"))]
+            decoder: None,
+            #[cfg(feature = "compressed")]
+            decompress: FALSE,
+            #[cfg(any(feature = "live_json", feature = "live_ron"))]
+            semantic: FALSE,
+            stop: None,
+        }
     }
 
     /// Set timeout. so if the file renamed/removed permanently, the watcher will be able to terminate itself.
     ///
-    /// default value is 0.63 seconds, set large than that if intended for watching huge file.
-    pub fn set_timeout(mut self, duration: f32) -> Watch {
-        if duration > TIMEOUT {
-            self.timeout = duration;
+    /// default value is 630 milliseconds, set large than that if intended for watching huge file (see also set_memory_cap).
+    pub fn set_timeout(mut self, duration: Duration) -> Watch {
+        self.timeout = duration;
+        self
+    }
+
+    /// Set how the watcher retries while the file is missing, overrides the retries derived from the timeout.
+    pub fn set_retry_policy(mut self, policy: RetryPolicy) -> Watch {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// The retry policy in use, by default 8 attempts with a fixed delay of timeout / 7.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match &self.retry {
+            Some(policy) => policy.clone(),
+            None => {
+                RetryPolicy::fixed(self.timeout / BREAK_POINT as u32).max_attempts(BREAK_POINT + 1)
+            }
         }
+    }
+
+    /// Set where change notifications come from, default is Backend::Native.
+    ///
+    /// use Backend::Poll or Backend::Auto for network/FUSE/bind mounts where native notifications never fire.
+    pub fn set_backend(mut self, backend: Backend) -> Watch {
+        self.backend = backend;
         self
     }
 
+    /// Follow symlinks: watch the link, every hop of the chain and the final target,
+    /// re-resolving the chain whenever a link is repointed (e.g. Kubernetes ConfigMap mounts swapping `..data`).
+    ///
+    /// a repointed link is reported as a single Changed, only if the content it leads to differs.
+    /// That's how single_file and LiveJson/LiveRon (reload_from_json_with...) follow a key of a ConfigMap/Secret volume
+    /// (`mount/key -> ..data/key`), see config_map to watch every key of a volume.
+    pub fn set_follow_symlinks(mut self, follow: bool) -> Watch {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Also report permission, ownership, mtime and size changes with Fstate::MetadataChanged,
+    /// e.g. a chmod making the file unreadable before a later read fails.
+    ///
+    /// a write changing the content comes with a MetadataChanged (mtime, size) after its Changed.
+    pub fn set_track_metadata(mut self, track: bool) -> Watch {
+        self.track_metadata = track;
+        self
+    }
+
+    /// Keep at most this many bytes of a watched file in memory, larger files are compared by length and FNV-1a hash only.
+    ///
+    /// files are always read and compared in blocks, by default the whole content is kept to compare against.
+    pub fn set_memory_cap(mut self, bytes: u64) -> Watch {
+        self.memory_cap = Some(bytes);
+        self
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    /// Keep the sensitive values of live json/ron files out of deserialization errors, schema violations and logs.
+    pub fn set_redaction(mut self, redaction: Redaction) -> Watch {
+        self.redaction = Some(Arc::new(redaction));
+        self
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    /// Compare `.json`/`.ron` files by their parsed values instead of their bytes, so reformatting, comments
+    /// or reordered map entries aren't reported as Changed (nor reloaded).
+    ///
+    /// a changed file is parsed once more to compare it, a file that doesn't parse is always a change.
+    pub fn set_semantic(mut self, semantic: bool) -> Watch {
+        self.semantic = semantic;
+        self
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    /// Decode live json/ron files, sections, graph inputs and assets (e.g. decrypt them with AesGcm or Age) between reading and deserializing them.
+    ///
+    /// a file that fails to decode is reported like one that fails to deserialize, the previous value is kept.
+    pub fn set_decoder<D: Decoder + 'static>(mut self, decoder: D) -> Watch {
+        self.decoder = Some(Arc::new(decoder));
+        self
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    /// Deserialization failures keep the previous value, only reported with the `tracing` feature.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    #[inline]
+    fn parse_failed<V: Format>(&self, path: &Path, error: &dyn std::fmt::Display) {
+        #[cfg(feature = "tracing")]
+        let error = match &self.redaction {
+            Some(redaction) => redaction.scrub_file::<V>(self.open(path), &error.to_string()),
+            None => error.to_string(),
+        };
+        warn!(path = %path.display(), error = %error, "failed to deserialize");
+    }
+
+    #[cfg(feature = "testing")]
+    /// Run the watchers against the given virtual filesystem and clock instead of the real ones.
+    pub fn set_fs(mut self, fs: &testing::VirtualFs) -> Watch {
+        self.env = fs.env();
+        self
+    }
+
+    /// Compress data written back to the file the way the file is (`compressed` feature), so it still reads back.
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    #[cfg_attr(not(feature = "compressed"), allow(unused_variables))]
+    fn recompress(&self, file: &Path, data: Vec<u8>) -> std::io::Result<Vec<u8>> {
+        #[cfg(feature = "compressed")]
+        let kind = match self.decompress {
+            TRUE => self
+                .env
+                .open(file)
+                .ok()
+                .and_then(|mut raw| compress::Kind::of(&mut *raw)),
+            FALSE => None,
+        };
+        #[cfg(feature = "compressed")]
+        let data = match kind {
+            Some(kind) => kind.compress(&data)?,
+            None => data,
+        };
+        Ok(data)
+    }
+
+    #[cfg(feature = "compressed")]
+    /// Decompress gzip and zstd files (detected by their magic bytes, not the extension) before comparing and deserializing them, off by default.
+    ///
+    /// a recompressed file with the same uncompressed data isn't a change.
+    pub fn set_decompress(mut self, decompress: bool) -> Watch {
+        self.decompress = decompress;
+        self
+    }
+
+    /// Open the file through the filesystem this watch runs against, decoded then decompressed.
+    #[cfg(any(
+        feature = "live_json",
+        feature = "live_ron",
+        feature = "graph",
+        feature = "assets"
+    ))]
+    #[inline]
+    fn open(&self, file: &Path) -> std::io::Result<Box<dyn std::io::Read + Send>> {
+        #[cfg(any(feature = "live_json", feature = "live_ron"))]
+        let data = decode::open(&*self.env, &self.decoder, file)?;
+        #[cfg(not(any(feature = "live_json", feature = "live_ron")))]
+        let data = self.env.open(file)?;
+        #[cfg(feature = "compressed")]
+        let data: Box<dyn std::io::Read + Send> = match self.decompress {
+            TRUE => Box::new(compress::Decompressed::new(data)),
+            FALSE => data,
+        };
+        Ok(data)
+    }
+
+    /// Keep waiting for the file to be recreated instead of terminating after the timeout.
+    ///
+    /// emits Removed once the file is gone for longer than the timeout, then Created (and Changed if the data differs) when it comes back,
+    /// max_wait None waits forever, otherwise NotFound is emitted and the watcher terminates after max_wait.
+    pub fn set_persistent(mut self, max_wait: Option<Duration>) -> Watch {
+        self.persistent = TRUE;
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Snapshot of the counters shared by this watch, its clones and every watcher spawned from them.
+    pub fn stats(&self) -> Stats {
+        self.counters.snapshot()
+    }
+
     /// Single file watcher
     #[inline]
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes, clippy::ptr_arg)]
     pub fn single_file<'a>(&self, file: &'a PathBuf, tx: Sender<Fstate<PathBuf>>) -> Result<bool> {
-        let timeout = self.timeout;
-        if file.exists() {
+        let opts = self.clone();
+        if self.env.exists(file) {
             let file = file.clone();
+            self.env.enter();
             spawn(move || {
-                watch(file, tx, timeout).expect("error occured while spawning watcher.");
+                watch(file.clone(), file, tx, opts).expect("error occured while spawning watcher.");
             });
             Ok(TRUE)
         } else {
@@ -194,11 +662,10 @@ impl Watch {
             tmp_vec_files = vec_files
                 .clone()
                 .into_iter()
-                .filter(|path| path.is_file())
+                .filter(|path| self.env.is_file(path))
                 .collect::<Vec<_>>();
         }
         if tmp_vec_files.len() > ZERO {
-            let timeout = self.timeout;
             *vec_files = tmp_vec_files;
             let mut vec_transmitter = Vec::new();
             {
@@ -209,8 +676,11 @@ impl Watch {
             for (i, tx) in vec_transmitter.iter().enumerate() {
                 let tx = tx.to_owned();
                 let file = vec_files[i].clone();
+                let opts = self.clone();
+                self.env.enter();
                 spawn(move || {
-                    watch(file, tx, timeout).expect("error occured while spawning watcher.");
+                    watch(file.clone(), file, tx, opts)
+                        .expect("error occured while spawning watcher.");
                 });
             }
             Ok(TRUE)
@@ -219,8 +689,70 @@ impl Watch {
         }
     }
 
+    /// Kubernetes ConfigMap/Secret volume watcher, false if the directory isn't laid out as one (no `..data` link).
+    ///
+    /// on every `..data` swap each key is compared against its previous content: Changed for the keys that differ,
+    /// Created/Removed for keys added to/dropped from the volume. NotFound(mount) once the volume is gone.
+    pub fn config_map(&self, mount: &Path, tx: Sender<Fstate<PathBuf>>) -> Result<bool> {
+        if configmap::is_volume(self, mount) {
+            let (mount, opts) = (mount.to_path_buf(), self.clone());
+            self.env.enter();
+            spawn(move || {
+                configmap::watch_volume(mount, tx, opts)
+                    .expect("error occured while spawning watcher.");
+            });
+            Ok(TRUE)
+        } else {
+            Ok(FALSE)
+        }
+    }
+
+    /// Watch a set of files that can grow and shrink while running, every watcher reports to tx
+    /// with the tag its file was registered with (the path itself for WatchSet<PathBuf>).
+    pub fn watch_set<T: Clone + Send + 'static>(&self, tx: Sender<Fstate<T>>) -> WatchSet<T> {
+        WatchSet::new(self.clone(), tx)
+    }
+
+    #[cfg(feature = "assets")]
+    /// Registry of hot reloaded assets decoded by typed loaders, reporting reloads and failures of every asset to tx.
+    ///
+    /// the files are watched in persistent mode, see AssetServer::load.
+    pub fn asset_server(&self, tx: Sender<AssetEvent>) -> AssetServer {
+        AssetServer::new(self, tx)
+    }
+
+    #[cfg(feature = "graph")]
+    /// Graph of live inputs and values derived from them, recomputed in order when their inputs change, see Graph::update.
+    ///
+    /// the inputs are watched in persistent mode.
+    pub fn graph(&self) -> Graph {
+        Graph::new(self)
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    /// Split one json (serde_json::Value) or ron (ron::Value) file into sections bound to their own types, see Sections::bind.
+    ///
+    /// the file is watched once in persistent mode, Err if it doesn't exist or can't be watched.
+    pub fn sections<V: Format>(&self, file: &Path) -> Result<Sections<V>> {
+        Sections::new(self, file)
+    }
+
+    #[cfg(feature = "live_dylib")]
+    /// Load the dynamic library (a `cdylib`) and reload it whenever it's rebuilt, see LiveDylib::update.
+    ///
+    /// the library is watched in persistent mode, state is the host side data handed from one version to the next.
+    pub fn live_dylib<S>(
+        &self,
+        library: &Path,
+        state: S,
+    ) -> std::result::Result<LiveDylib<S>, DylibError> {
+        LiveDylib::new(library, self, state)
+    }
+
     /// Additional for multiple_files to check if there's watcher(s) still continue watching the file(s).
     #[inline]
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
     pub fn is_continue<'a>(vec_files: &mut Vec<PathBuf>, file: &'a PathBuf) -> bool {
         let tmp_files = vec_files.clone();
         if !file.exists() {
@@ -238,17 +770,19 @@ impl Watch {
 
     #[cfg(feature = "live_json")]
     /// (Optional, if needed) reinit mutable stuct before calling fn json_de.
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes, clippy::ptr_arg)]
     pub fn reinit_de_json<'a, T>(&mut self, mut_struct: &mut T, json: &'a PathBuf)
     where
         T: serde::de::DeserializeOwned,
     {
-        if let Ok(file) = File::open(json) {
-            match serde_json::from_reader(BufReader::new(file)) {
+        if let Ok(file) = self.open(json) {
+            match serde_json::from_reader(file) {
                 Ok(loaded) => {
                     // if implemented from missing members, rust analyzer usually will change *self to *quadoculars (this crate), just change it back from *quadoculars to *self
                     *mut_struct = loaded;
                 }
-                _ => (),
+                Err(e) => self.parse_failed::<Value>(json, &e),
             }
         }
     }
@@ -256,11 +790,18 @@ impl Watch {
     #[cfg(feature = "live_json")]
     /// Live reload DeserializeOwned struct from json.
     #[inline]
+    // released signature and body, kept as is.
+    #[allow(
+        clippy::needless_lifetimes,
+        clippy::ptr_arg,
+        clippy::single_match,
+        clippy::collapsible_match
+    )]
     pub fn de_json<'a, T>(&self, mut_struct: &mut T, json: &'a PathBuf) -> Result<bool>
     where
         T: serde::de::DeserializeOwned,
     {
-        let timeout = self.timeout;
+        let opts = self.clone();
         cfg_if! {
             if  #[cfg(feature = "crossbeam_channel")] {
                let (tx, rx) = bounded(ZERO);
@@ -271,20 +812,21 @@ impl Watch {
             }
         }
 
-        if json.exists() {
+        if self.env.exists(json) {
             let json = json.clone();
+            self.env.enter();
             spawn(move || {
-                watch(json, tx, timeout).expect("error occured while spawning watcher.");
+                watch(json.clone(), json, tx, opts).expect("error occured while spawning watcher.");
             });
             if let Ok(state) = rx.recv() {
                 match state {
                     Fstate::Changed(json) => {
-                        let mut reader = BufReader::new(File::open(json)?);
+                        let mut reader = self.open(&json)?;
                         match serde_json::from_reader(&mut reader) {
                             Ok(new_data) => {
                                 *mut_struct = new_data;
                             }
-                            _ => (),
+                            Err(e) => self.parse_failed::<Value>(&json, &e),
                         }
                     }
                     _ => (),
@@ -298,13 +840,15 @@ impl Watch {
 
     #[cfg(feature = "live_json")]
     /// (Optional, if needed) Initialize empty json Value before calling fn json_val.
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes, clippy::ptr_arg)]
     pub fn json_val_init<'a>(&self, json: &'a PathBuf, val: &mut Value) {
-        if let Ok(file) = File::open(json) {
-            match serde_json::from_reader(BufReader::new(file)) {
+        if let Ok(file) = self.open(json) {
+            match serde_json::from_reader(file) {
                 Ok(loaded) => {
                     *val = loaded;
                 }
-                _ => (),
+                Err(e) => self.parse_failed::<Value>(json, &e),
             }
         }
     }
@@ -312,8 +856,15 @@ impl Watch {
     #[cfg(feature = "live_json")]
     /// Live reload serde_json Value.
     #[inline]
+    // released signature and body, kept as is.
+    #[allow(
+        clippy::needless_lifetimes,
+        clippy::ptr_arg,
+        clippy::single_match,
+        clippy::collapsible_match
+    )]
     pub fn json_val<'a>(&self, json: &'a PathBuf, val: &mut Value) -> Result<bool> {
-        let timeout = self.timeout;
+        let opts = self.clone();
         cfg_if! {
             if  #[cfg(feature = "crossbeam_channel")] {
                let (tx, rx) = bounded(ZERO);
@@ -323,20 +874,21 @@ impl Watch {
                let (tx, rx) = std::sync::mpsc::channel();
             }
         }
-        if json.exists() {
+        if self.env.exists(json) {
             let json = json.clone();
+            self.env.enter();
             spawn(move || {
-                watch(json, tx, timeout).expect("error occured while spawning watcher.");
+                watch(json.clone(), json, tx, opts).expect("error occured while spawning watcher.");
             });
             if let Ok(state) = rx.recv() {
                 match state {
                     Fstate::Changed(json) => {
-                        let mut reader = BufReader::new(File::open(json)?);
+                        let mut reader = self.open(&json)?;
                         match serde_json::from_reader(&mut reader) {
                             Ok(new_data) => {
                                 *val = new_data;
                             }
-                            _ => (),
+                            Err(e) => self.parse_failed::<Value>(&json, &e),
                         }
                     }
                     _ => (),
@@ -348,18 +900,154 @@ impl Watch {
         }
     }
 
+    #[cfg(feature = "live_json")]
+    /// (Optional, if needed) reinit mutable stuct before calling fn de_json_migrated.
+    pub fn reinit_de_json_migrated<T>(
+        &self,
+        mut_struct: &mut T,
+        json: &Path,
+        migrations: &Migrations<serde_json::Value>,
+    ) where
+        T: serde::de::DeserializeOwned,
+    {
+        match migrations.load(self, json) {
+            Ok(loaded) => *mut_struct = loaded,
+            Err(e) => self.parse_failed::<Value>(json, &e),
+        }
+    }
+
+    #[cfg(feature = "live_json")]
+    /// Live reload DeserializeOwned struct from json, upgraded from older versions by the migrations first.
+    #[inline]
+    pub fn de_json_migrated<T>(
+        &self,
+        mut_struct: &mut T,
+        json: &Path,
+        migrations: &Migrations<serde_json::Value>,
+    ) -> Result<bool>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let opts = self.clone();
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = bounded(ZERO);
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = bounded(ZERO);
+            }  else {
+               let (tx, rx) = std::sync::mpsc::channel();
+            }
+        }
+
+        if self.env.exists(json) {
+            let json = json.to_path_buf();
+            self.env.enter();
+            spawn(move || {
+                watch(json.clone(), json, tx, opts).expect("error occured while spawning watcher.");
+            });
+            if let Ok(Fstate::Changed(json)) = rx.recv() {
+                self.reinit_de_json_migrated(mut_struct, &json, migrations)
+            }
+            Ok(TRUE)
+        } else {
+            Ok(FALSE)
+        }
+    }
+
+    #[cfg(feature = "json_schema")]
+    /// (Optional, if needed) Initialize json Value before calling fn json_val_with_schema, left as is if it doesn't match the schema.
+    pub fn json_val_init_with_schema(
+        &self,
+        json: &Path,
+        val: &mut Value,
+        schema: &Schema,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        self.load_json_val(json, val, schema, violations)
+    }
+
+    #[cfg(feature = "json_schema")]
+    /// Live reload serde_json Value, rejecting values that don't match the schema.
+    ///
+    /// val keeps the last valid value and violations gets every violation of the rejected one, it's cleared once a value is accepted.
+    #[inline]
+    pub fn json_val_with_schema(
+        &self,
+        json: &Path,
+        val: &mut Value,
+        schema: &Schema,
+        violations: &mut Vec<SchemaViolation>,
+    ) -> Result<bool> {
+        let opts = self.clone();
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = bounded(ZERO);
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = bounded(ZERO);
+            }  else {
+               let (tx, rx) = std::sync::mpsc::channel();
+            }
+        }
+        if self.env.exists(json) {
+            let json = json.to_path_buf();
+            self.env.enter();
+            spawn(move || {
+                watch(json.clone(), json, tx, opts).expect("error occured while spawning watcher.");
+            });
+            if let Ok(Fstate::Changed(json)) = rx.recv() {
+                self.load_json_val(&json, val, schema, violations);
+            }
+            Ok(TRUE)
+        } else {
+            Ok(FALSE)
+        }
+    }
+
+    #[cfg(feature = "json_schema")]
+    fn load_json_val(
+        &self,
+        json: &Path,
+        val: &mut Value,
+        schema: &Schema,
+        violations: &mut Vec<SchemaViolation>,
+    ) {
+        if let Ok(file) = self.open(json) {
+            match serde_json::from_reader(file) {
+                Ok(loaded) => {
+                    let mut rejected = schema.validate(&loaded);
+                    if let Some(redaction) = &self.redaction {
+                        for violation in &mut rejected {
+                            violation.message = redaction.scrub(&loaded, &violation.message);
+                        }
+                    }
+                    if rejected.is_empty() {
+                        *val = loaded;
+                    }
+                    #[cfg(feature = "tracing")]
+                    for violation in &rejected {
+                        warn!(path = %json.display(), %violation, "json rejected by its schema");
+                    }
+                    *violations = rejected;
+                }
+                Err(e) => self.parse_failed::<Value>(json, &e),
+            }
+        }
+    }
+
     #[cfg(feature = "live_ron")]
     /// (Optional, if needed) reinit mutable stuct before calling fn de_ron.
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes, clippy::ptr_arg)]
     pub fn reinit_de_ron<'a, T>(&mut self, mut_struct: &mut T, ron: &'a PathBuf)
     where
         T: serde::de::DeserializeOwned,
     {
-        if let Ok(file) = File::open(ron) {
-            match ron::de::from_reader(BufReader::new(file)) {
+        if let Ok(file) = self.open(ron) {
+            match ron::de::from_reader(file) {
                 Ok(loaded) => {
                     *mut_struct = loaded;
                 }
-                _ => (),
+                Err(e) => self.parse_failed::<ron::Value>(ron, &e),
             }
         }
     }
@@ -367,11 +1055,18 @@ impl Watch {
     #[cfg(feature = "live_ron")]
     /// Live reload DeserializeOwned struct from ron.
     #[inline]
+    // released signature and body, kept as is.
+    #[allow(
+        clippy::needless_lifetimes,
+        clippy::ptr_arg,
+        clippy::single_match,
+        clippy::collapsible_match
+    )]
     pub fn de_ron<'a, T>(&self, mut_struct: &mut T, ron: &'a PathBuf) -> Result<bool>
     where
         T: serde::de::DeserializeOwned,
     {
-        let timeout = self.timeout;
+        let opts = self.clone();
         cfg_if! {
             if  #[cfg(feature = "crossbeam_channel")] {
                let (tx, rx) = bounded(ZERO);
@@ -382,20 +1077,21 @@ impl Watch {
             }
         }
 
-        if ron.exists() {
+        if self.env.exists(ron) {
             let ron = ron.clone();
+            self.env.enter();
             spawn(move || {
-                watch(ron, tx, timeout).expect("error occured while spawning watcher.");
+                watch(ron.clone(), ron, tx, opts).expect("error occured while spawning watcher.");
             });
             if let Ok(state) = rx.recv() {
                 match state {
                     Fstate::Changed(ron) => {
-                        let mut reader = BufReader::new(File::open(ron)?);
+                        let mut reader = self.open(&ron)?;
                         match ron::de::from_reader(&mut reader) {
                             Ok(new_data) => {
                                 *mut_struct = new_data;
                             }
-                            _ => (),
+                            Err(e) => self.parse_failed::<ron::Value>(&ron, &e),
                         }
                     }
                     _ => (),
@@ -406,39 +1102,110 @@ impl Watch {
             Ok(FALSE)
         }
     }
+
+    #[cfg(feature = "live_ron")]
+    /// (Optional, if needed) reinit mutable stuct before calling fn de_ron_migrated.
+    pub fn reinit_de_ron_migrated<T>(
+        &self,
+        mut_struct: &mut T,
+        ron: &Path,
+        migrations: &Migrations<ron::Value>,
+    ) where
+        T: serde::de::DeserializeOwned,
+    {
+        match migrations.load(self, ron) {
+            Ok(loaded) => *mut_struct = loaded,
+            Err(e) => self.parse_failed::<ron::Value>(ron, &e),
+        }
+    }
+
+    #[cfg(feature = "live_ron")]
+    /// Live reload DeserializeOwned struct from ron, upgraded from older versions by the migrations first.
+    #[inline]
+    pub fn de_ron_migrated<T>(
+        &self,
+        mut_struct: &mut T,
+        ron: &Path,
+        migrations: &Migrations<ron::Value>,
+    ) -> Result<bool>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let opts = self.clone();
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = bounded(ZERO);
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = bounded(ZERO);
+            }  else {
+               let (tx, rx) = std::sync::mpsc::channel();
+            }
+        }
+
+        if self.env.exists(ron) {
+            let ron = ron.to_path_buf();
+            self.env.enter();
+            spawn(move || {
+                watch(ron.clone(), ron, tx, opts).expect("error occured while spawning watcher.");
+            });
+            if let Ok(Fstate::Changed(ron)) = rx.recv() {
+                self.reinit_de_ron_migrated(mut_struct, &ron, migrations)
+            }
+            Ok(TRUE)
+        } else {
+            Ok(FALSE)
+        }
+    }
 }
 
 #[cfg(feature = "live_json")]
 /// Instant trait for live reloading json values for DeserializeOwned struct,
 pub trait LiveJson {
     /// (Optional) reinit mutable stuct if needed.
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
     fn reinit_from_json<'a>(&mut self, json: &'a PathBuf)
     where
         Self: serde::de::DeserializeOwned,
     {
-        if let Ok(file) = File::open(json) {
-            match serde_json::from_reader(BufReader::new(file)) {
-                Ok(loaded) => {
-                    // if implemented from missing members, rust analyzer usually will change *self to *quadoculars (this crate), just change it back from *quadoculars to *self
-                    *self = loaded;
-                }
-                _ => (),
-            }
-        }
+        self.reinit_from_json_with(json, &Watch::new())
+    }
+
+    /// Same as reinit_from_json, reading through the given watch (e.g. one running against testing::VirtualFs).
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
+    fn reinit_from_json_with<'a>(&mut self, json: &'a PathBuf, watch: &Watch)
+    where
+        Self: serde::de::DeserializeOwned,
+    {
+        watch.clone().reinit_de_json(self, json)
     }
 
     /// Start live reload mutable stuct,
     ///
-    /// Note: default timeout value is 0.63 seconds.
+    /// Note: default timeout value is 630 milliseconds.
     ///
     /// if the json file renamed/removed permanently, the watcher will terminate itself according to the given timeout value.
     #[inline]
-    fn reload_from_json<'a>(&mut self, json: &'a PathBuf, timeout: f32) -> Result<bool>
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
+    fn reload_from_json<'a>(&mut self, json: &'a PathBuf, timeout: Duration) -> Result<bool>
+    where
+        Self: serde::de::DeserializeOwned,
+    {
+        self.reload_from_json_with(json, &Watch::new().set_timeout(timeout))
+    }
+
+    /// Same as reload_from_json, with the given watch settings (persistent mode, backend, retry policy, testing fs...).
+    #[inline]
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
+    fn reload_from_json_with<'a>(&mut self, json: &'a PathBuf, watch: &Watch) -> Result<bool>
     where
         Self: serde::de::DeserializeOwned,
     {
         // and this, change input mut_struct: from quadoculars to self
-        Watch::new().set_timeout(timeout).de_json(self, json)
+        watch.de_json(self, json)
     }
 }
 
@@ -446,33 +1213,50 @@ pub trait LiveJson {
 /// Instant trait for live reloading ron values for DeserializeOwned struct.
 pub trait LiveRon {
     /// (Optional) reinit mutable stuct if needed.
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
     fn reinit_from_ron<'a>(&mut self, ron: &'a PathBuf)
     where
         Self: serde::de::DeserializeOwned,
     {
-        if let Ok(file) = File::open(ron) {
-            match ron::de::from_reader(BufReader::new(file)) {
-                Ok(loaded) => {
-                    // if implemented from missing members, rust analyzer usually will change *self to *quadoculars (this crate), just change it back from *quadoculars to *self
-                    *self = loaded;
-                }
-                _ => (),
-            }
-        }
+        self.reinit_from_ron_with(ron, &Watch::new())
+    }
+
+    /// Same as reinit_from_ron, reading through the given watch (e.g. one running against testing::VirtualFs).
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
+    fn reinit_from_ron_with<'a>(&mut self, ron: &'a PathBuf, watch: &Watch)
+    where
+        Self: serde::de::DeserializeOwned,
+    {
+        watch.clone().reinit_de_ron(self, ron)
     }
 
     /// Start live reload mutable stuct,
     ///
-    /// Note: default timeout value is 0.63 seconds.
+    /// Note: default timeout value is 630 milliseconds.
     ///
     /// if the ron file renamed/removed permanently, the watcher will terminate itself according to the given timeout value.
     #[inline]
-    fn reload_from_ron<'a>(&mut self, ron: &'a PathBuf, timeout: f32) -> Result<bool>
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
+    fn reload_from_ron<'a>(&mut self, ron: &'a PathBuf, timeout: Duration) -> Result<bool>
+    where
+        Self: serde::de::DeserializeOwned,
+    {
+        self.reload_from_ron_with(ron, &Watch::new().set_timeout(timeout))
+    }
+
+    /// Same as reload_from_ron, with the given watch settings (persistent mode, backend, retry policy, testing fs...).
+    #[inline]
+    // released signature, kept for compatibility.
+    #[allow(clippy::needless_lifetimes)]
+    fn reload_from_ron_with<'a>(&mut self, ron: &'a PathBuf, watch: &Watch) -> Result<bool>
     where
         Self: serde::de::DeserializeOwned,
     {
         // and this, change input mut_struct: from quadoculars to self
-        Watch::new().set_timeout(timeout).de_ron(self, ron)
+        watch.de_ron(self, ron)
     }
 }
 
@@ -483,17 +1267,26 @@ mod tests {
     use cfg_if::cfg_if;
     cfg_if! {
        if #[cfg(feature = "crossbeam_channel")] {
-           use crossbeam_channel::{bounded, Sender};
+           use crossbeam_channel::{bounded, unbounded, Sender};
        } else if #[cfg(feature = "flume_channel")] {
-           use flume::{bounded, Sender};
+           use flume::{bounded, unbounded, Sender};
        } else {
-           use std::sync::mpsc::{sync_channel, Sender};
+           use std::sync::mpsc::{channel, sync_channel, Sender};
        }
     }
-    use std::{env, str::FromStr};
+    use std::{env, fs, str::FromStr, thread::sleep};
+
+    /// A fresh `$TMPDIR/quadoculars_test_<name>`, for the tests that need the real filesystem.
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("quadoculars_test_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
     #[test]
-    #[allow(unused_variables)]
+    // one of the crate's original tests, kept as written.
+    #[allow(unused_variables, clippy::never_loop, clippy::bool_assert_comparison)]
     fn test_if_file_not_exist() {
         cfg_if! {
             if  #[cfg(feature = "crossbeam_channel")] {
@@ -508,7 +1301,7 @@ mod tests {
         let file_not_exist = PathBuf::from_str("file.not_exist").unwrap();
 
         while let Ok(is_watching_file_not_yet_exist) = Watch::new()
-            .set_timeout(0.6)
+            .set_timeout(Duration::from_millis(600))
             .single_file(&file_not_exist, tx.to_owned())
         {
             assert_eq!(is_watching_file_not_yet_exist, false);
@@ -517,7 +1310,8 @@ mod tests {
     }
 
     #[test]
-    #[allow(unused_variables)]
+    // one of the crate's original tests, kept as written.
+    #[allow(unused_variables, clippy::never_loop, clippy::bool_assert_comparison)]
     fn test_if_file_exist() {
         cfg_if! {
             if  #[cfg(feature = "crossbeam_channel")] {
@@ -538,11 +1332,323 @@ mod tests {
         }
 
         while let Ok(is_watching_cargo_toml) = Watch::new()
-            .set_timeout(0.6)
+            .set_timeout(Duration::from_millis(600))
             .single_file(&file_exist, tx.to_owned())
         {
             assert_eq!(is_watching_cargo_toml, true);
             break;
         }
     }
+
+    #[test]
+    fn test_rename_over() {
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = unbounded();
+            }  else {
+               let (tx, rx) = channel();
+            }
+        }
+
+        let dir = temp_dir("rename_over");
+        let file = dir.join("config.json");
+        let tmp = dir.join("config.json.tmp");
+        fs::write(&file, "{\"a\": 1}").unwrap();
+
+        assert!(Watch::new().single_file(&file, tx).unwrap());
+        sleep(Duration::from_millis(100));
+
+        // save the way vim/jetbrains do: write a temp file, then rename it over the original.
+        fs::write(&tmp, "{\"a\": 2}").unwrap();
+        fs::rename(&tmp, &file).unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("rename over the file is not reported"),
+        }
+
+        // the watch is re-armed on the new inode, identical data is not reported.
+        fs::write(&tmp, "{\"a\": 2}").unwrap();
+        fs::rename(&tmp, &file).unwrap();
+        fs::write(&file, "{\"a\": 3}").unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("write after rename over is not reported"),
+        }
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() {
+        use std::os::unix::fs::symlink;
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = unbounded();
+            }  else {
+               let (tx, rx) = channel();
+            }
+        }
+
+        let dir = temp_dir("follow_symlinks");
+        fs::create_dir_all(dir.join("v1")).unwrap();
+        fs::create_dir_all(dir.join("v2")).unwrap();
+        fs::write(dir.join("v1/config.json"), "{\"a\": 1}").unwrap();
+        fs::write(dir.join("v2/config.json"), "{\"a\": 2}").unwrap();
+        // config.json -> current/config.json, current -> v1
+        symlink("v1", dir.join("current")).unwrap();
+        symlink("current/config.json", dir.join("config.json")).unwrap();
+        let file = dir.join("config.json");
+
+        let watch = Watch::new().set_follow_symlinks(TRUE);
+        assert!(watch.single_file(&file, tx).unwrap());
+        sleep(Duration::from_millis(100));
+
+        // the target is written in place.
+        fs::write(dir.join("v1/config.json"), "{\"a\": 11}").unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("write to the link target is not reported"),
+        }
+
+        // repoint the directory link atomically, once.
+        symlink("v2", dir.join("current.tmp")).unwrap();
+        fs::rename(dir.join("current.tmp"), dir.join("current")).unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("repointed link is not reported"),
+        }
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        // the new target is watched, the old one isn't.
+        fs::write(dir.join("v1/config.json"), "{\"a\": 111}").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        fs::write(dir.join("v2/config.json"), "{\"a\": 22}").unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("write to the new link target is not reported"),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_config_map() {
+        use std::os::unix::fs::symlink;
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = unbounded();
+               let (tx1, rx1) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = unbounded();
+               let (tx1, rx1) = unbounded();
+            }  else {
+               let (tx, rx) = channel();
+               let (tx1, rx1) = channel();
+            }
+        }
+
+        let mount = temp_dir("config_map");
+        // what the kubelet does on every update: new timestamped directory, `..data` renamed over.
+        let update = |version: &str, keys: &[(&str, &str)]| {
+            fs::create_dir_all(mount.join(version)).unwrap();
+            for (key, data) in keys {
+                fs::write(mount.join(version).join(key), data).unwrap();
+                let _ = symlink(format!("..data/{}", key), mount.join(key));
+            }
+            symlink(version, mount.join("..data_tmp")).unwrap();
+            fs::rename(mount.join("..data_tmp"), mount.join("..data")).unwrap();
+        };
+        update("..v1", &[("app.json", "{\"a\": 1}"), ("log.json", "{}")]);
+        let (app, log) = (mount.join("app.json"), mount.join("log.json"));
+
+        let watch = Watch::new();
+        assert!(!watch.config_map(&env::temp_dir(), tx.clone()).unwrap());
+        assert!(watch.config_map(&mount, tx).unwrap());
+        assert!(watch
+            .clone()
+            .set_follow_symlinks(TRUE)
+            .single_file(&app, tx1)
+            .unwrap());
+        sleep(Duration::from_millis(100));
+
+        update("..v2", &[("app.json", "{\"a\": 2}"), ("log.json", "{}")]);
+        fs::remove_dir_all(mount.join("..v1")).unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, app),
+            _ => panic!("..data swap is not reported"),
+        }
+        match rx1.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, app),
+            _ => panic!("..data swap is not reported for the key"),
+        }
+        // log.json is identical.
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+        assert!(rx1.try_recv().is_err());
+
+        fs::remove_file(&log).unwrap();
+        update("..v3", &[("app.json", "{\"a\": 2}"), ("db.json", "{}")]);
+        let mut states = Vec::new();
+        while let Ok(state) = rx.recv_timeout(Duration::from_millis(500)) {
+            states.push(match state {
+                Fstate::Created(key) => ("created", key),
+                Fstate::Removed(key) => ("removed", key),
+                _ => panic!("only db.json and log.json changed"),
+            });
+        }
+        states.sort();
+        assert_eq!(
+            states,
+            [("created", mount.join("db.json")), ("removed", log)]
+        );
+
+        fs::remove_dir_all(&mount).unwrap();
+    }
+
+    #[test]
+    fn test_persistent() {
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = unbounded();
+            }  else {
+               let (tx, rx) = channel();
+            }
+        }
+
+        let dir = temp_dir("persistent");
+        let file = dir.join("output.bin");
+        fs::write(&file, "first build").unwrap();
+
+        assert!(Watch::new()
+            .set_persistent(None)
+            .single_file(&file, tx)
+            .unwrap());
+        sleep(Duration::from_millis(100));
+
+        // removed for longer than the timeout, then regenerated.
+        fs::remove_file(&file).unwrap();
+        match rx.recv_timeout(Duration::from_secs(3)) {
+            Ok(Fstate::Removed(removed)) => assert_eq!(removed, file),
+            _ => panic!("removal is not reported"),
+        }
+        fs::write(&file, "second build").unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Created(created)) => assert_eq!(created, file),
+            _ => panic!("recreation is not reported"),
+        }
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("new data is not reported"),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_poll_backend() {
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = unbounded();
+            }  else {
+               let (tx, rx) = channel();
+            }
+        }
+
+        let dir = temp_dir("poll_backend");
+        let file = dir.join("mounted.ron");
+        fs::write(&file, "(a: 1)").unwrap();
+
+        assert!(Watch::new()
+            .set_backend(Backend::Poll(Duration::from_millis(50)))
+            .single_file(&file, tx)
+            .unwrap());
+        sleep(Duration::from_millis(100));
+
+        fs::write(&file, "(a: 10)").unwrap();
+        match rx.recv_timeout(Duration::from_secs(2)) {
+            Ok(Fstate::Changed(changed)) => assert_eq!(changed, file),
+            _ => panic!("polling doesn't report the change"),
+        }
+
+        fs::remove_file(&file).unwrap();
+        match rx.recv_timeout(Duration::from_secs(3)) {
+            Ok(Fstate::NotFound(not_found)) => assert_eq!(not_found, file),
+            _ => panic!("polling doesn't report the removal"),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_track_metadata() {
+        use crate::testing::VirtualFs;
+        cfg_if! {
+            if  #[cfg(feature = "crossbeam_channel")] {
+               let (tx, rx) = unbounded();
+            } else if #[cfg(feature = "flume_channel")] {
+               let (tx, rx) = unbounded();
+            }  else {
+               let (tx, rx) = channel();
+            }
+        }
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("secrets.json");
+        fs.write(&file, "{}");
+
+        let watch = Watch::new().set_fs(&fs).set_track_metadata(TRUE);
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        fs.chmod(&file, 0o000);
+        match rx.try_recv() {
+            Ok(Fstate::MetadataChanged { path, old, new }) => {
+                assert_eq!(path, file);
+                assert_eq!((old.mode, new.mode), (Some(0o644), Some(0o000)));
+                assert!(!old.readonly && new.readonly);
+            }
+            _ => panic!("chmod is not reported"),
+        }
+        fs.chown(&file, 0, 0);
+        assert!(
+            matches!(rx.try_recv(), Ok(Fstate::MetadataChanged { new, .. }) if new.uid == Some(0))
+        );
+
+        // content first, then mtime and size.
+        fs.advance(Duration::from_millis(10));
+        fs.write(&file, "{\"a\": 1}");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        match rx.try_recv() {
+            Ok(Fstate::MetadataChanged { old, new, .. }) => {
+                assert_eq!((old.len, new.len), (2, 8));
+                assert!(new.modified > old.modified);
+            }
+            _ => panic!("mtime and size changes are not reported"),
+        }
+        assert!(rx.try_recv().is_err());
+
+        // removed and recreated before the retries ran out: the new file is the reference.
+        fs.remove(&file);
+        fs.write(&file, "{\"a\": 1}");
+        assert!(rx.try_recv().is_err());
+        fs.chmod(&file, 0o600);
+        match rx.try_recv() {
+            Ok(Fstate::MetadataChanged { old, new, .. }) => {
+                assert_eq!((old.mode, new.mode), (Some(0o644), Some(0o600)));
+            }
+            _ => panic!("chmod of the new file is not reported"),
+        }
+        assert!(rx.try_recv().is_err());
+    }
 }
@@ -0,0 +1,133 @@
+//! Building blocks shared by the structures kept up to date from watched files (assets, graph, sections).
+use crate::{Fstate, Watch, WatchSet};
+use cfg_if::cfg_if;
+use notify::Result;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+cfg_if! {
+   if #[cfg(feature = "crossbeam_channel")] {
+       use crossbeam_channel::{unbounded as channel, Receiver};
+   } else if #[cfg(feature = "flume_channel")] {
+       use flume::{unbounded as channel, Receiver};
+   } else {
+       use std::sync::mpsc::{channel, Receiver};
+   }
+}
+
+/// Typed handle over an untyped id (a tuple struct of usize): Copy, Eq and Hash whatever T is.
+macro_rules! typed_handle {
+    ($(#[$meta:meta])* $vis:vis struct $handle:ident<T>($id:ident);) => {
+        $(#[$meta])*
+        $vis struct $handle<T> {
+            id: $id,
+            _type: std::marker::PhantomData<fn() -> T>,
+        }
+
+        impl<T> $handle<T> {
+            fn new(id: $id) -> $handle<T> {
+                Self {
+                    id,
+                    _type: std::marker::PhantomData,
+                }
+            }
+
+            pub fn id(&self) -> $id {
+                self.id
+            }
+        }
+
+        impl<T> Clone for $handle<T> {
+            fn clone(&self) -> Self {
+                *self
+            }
+        }
+
+        impl<T> Copy for $handle<T> {}
+
+        impl<T> PartialEq for $handle<T> {
+            fn eq(&self, other: &Self) -> bool {
+                self.id == other.id
+            }
+        }
+
+        impl<T> Eq for $handle<T> {}
+
+        impl<T> std::hash::Hash for $handle<T> {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                std::hash::Hash::hash(&self.id, state)
+            }
+        }
+
+        impl<T> std::fmt::Debug for $handle<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_tuple(stringify!($handle)).field(&self.id.0).finish()
+            }
+        }
+    };
+}
+
+/// The files a live structure reads, each watched until the structure is dropped.
+///
+/// Watchers are persistent: editors often save by removing the file and writing a new one,
+/// which must not end the watch. Only Changed matters to the readers, a Created is followed by
+/// a Changed when the new file differs from the last one read.
+pub(crate) struct LiveFiles {
+    set: WatchSet,
+    rx: Receiver<Fstate<PathBuf>>,
+}
+
+impl LiveFiles {
+    pub(crate) fn new(watch: &Watch) -> LiveFiles {
+        let (tx, rx) = channel();
+        Self {
+            set: watch.clone().set_persistent(None).watch_set(tx),
+            rx,
+        }
+    }
+
+    /// The underlying set, e.g. to add files from another thread.
+    #[cfg_attr(not(feature = "assets"), allow(dead_code))]
+    pub(crate) fn set(&self) -> &WatchSet {
+        &self.set
+    }
+
+    /// Watch the file, call it before reading the file the first time so no change in between is missed.
+    #[cfg_attr(
+        not(any(feature = "graph", feature = "live_json", feature = "live_ron")),
+        allow(dead_code)
+    )]
+    pub(crate) fn add(&self, path: &Path) -> Result<bool> {
+        self.set.add(path)
+    }
+
+    /// The files that changed since the last call, never blocks.
+    #[cfg_attr(
+        not(any(feature = "graph", feature = "live_json", feature = "live_ron")),
+        allow(dead_code)
+    )]
+    pub(crate) fn changed(&self) -> HashSet<PathBuf> {
+        let mut changed = HashSet::new();
+        while let Ok(state) = self.rx.try_recv() {
+            if let Fstate::Changed(path) = state {
+                changed.insert(path);
+            }
+        }
+        changed
+    }
+
+    /// The next state of any file, None if there was none within timeout.
+    #[cfg_attr(not(feature = "assets"), allow(dead_code))]
+    pub(crate) fn next(&self, timeout: Duration) -> Option<Fstate<PathBuf>> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+}
+
+impl Drop for LiveFiles {
+    fn drop(&mut self) {
+        self.set.clear();
+    }
+}
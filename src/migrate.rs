@@ -0,0 +1,281 @@
+//! Versioned config files, upgraded one version at a time on the dynamic value before deserializing it.
+use crate::{format::Format, Watch, FALSE};
+use std::{collections::BTreeMap, fmt, io::Read, path::Path};
+
+/// A dynamic value carrying its version in a field, serde_json::Value (live_json) and ron::Value (live_ron).
+pub trait Versioned: Format {
+    /// None if the value has no (integer) version field.
+    fn version(&self, field: &str) -> Option<u64>;
+
+    fn set_version(&mut self, field: &str, version: u64);
+}
+
+#[cfg(feature = "live_json")]
+impl Versioned for serde_json::Value {
+    fn version(&self, field: &str) -> Option<u64> {
+        self.get(field)?.as_u64()
+    }
+
+    fn set_version(&mut self, field: &str, version: u64) {
+        if let Some(object) = self.as_object_mut() {
+            object.insert(field.to_string(), version.into());
+        }
+    }
+}
+
+#[cfg(feature = "live_ron")]
+impl Versioned for ron::Value {
+    fn version(&self, field: &str) -> Option<u64> {
+        match self.select(field)? {
+            ron::Value::Number(number) => number.as_i64().filter(|v| *v >= 0).map(|v| v as u64),
+            _ => None,
+        }
+    }
+
+    fn set_version(&mut self, field: &str, version: u64) {
+        if let ron::Value::Map(map) = self {
+            let version = ron::Number::Integer(version as i64);
+            map.insert(
+                ron::Value::String(field.to_string()),
+                ron::Value::Number(version),
+            );
+        }
+    }
+}
+
+type Step<V> = Box<dyn Fn(&mut V) -> Result<(), String> + Send + Sync>;
+type Report = Box<dyn Fn(&Path, u64, u64) + Send + Sync>;
+
+/// Chain of migrations, each one upgrading the value from version N to N+1.
+///
+/// a value without a version field is version 0, the version field is bumped after every migration.
+pub struct Migrations<V> {
+    field: String,
+    steps: BTreeMap<u64, Step<V>>,
+    write_back: bool,
+    report: Option<Report>,
+}
+
+impl<V: Versioned> Default for Migrations<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Versioned> Migrations<V> {
+    /// No migrations, versions in the "version" field.
+    pub fn new() -> Migrations<V> {
+        Self {
+            field: "version".to_string(),
+            steps: BTreeMap::new(),
+            write_back: FALSE,
+            report: None,
+        }
+    }
+
+    /// Set the field holding the version.
+    pub fn set_field(mut self, field: &str) -> Migrations<V> {
+        self.field = field.to_string();
+        self
+    }
+
+    /// Add the migration from version `from` to `from + 1`, replacing any other for the same version.
+    pub fn step<F>(mut self, from: u64, migration: F) -> Migrations<V>
+    where
+        F: Fn(&mut V) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.steps.insert(from, Box::new(migration));
+        self
+    }
+
+    /// Write the upgraded value back to the file after a migration ran, so it's only migrated once.
+    ///
+    /// the file is rewritten pretty printed (comments and formatting are lost), which triggers another reload.
+    /// gzip/zstd files are compressed again the same way, files read through a decoder (Watch::set_decoder) are never written back.
+    pub fn set_write_back(mut self, write_back: bool) -> Migrations<V> {
+        self.write_back = write_back;
+        self
+    }
+
+    /// Called with the file and its old and new versions whenever migrations ran.
+    pub fn on_migrate<F: Fn(&Path, u64, u64) + Send + Sync + 'static>(
+        mut self,
+        report: F,
+    ) -> Migrations<V> {
+        self.report = Some(Box::new(report));
+        self
+    }
+
+    /// Upgrade the value as far as the chain goes, returns the versions it went from and to if any migration ran.
+    pub fn migrate(&self, path: &Path, value: &mut V) -> Result<Option<(u64, u64)>, String> {
+        let from = value.version(&self.field).unwrap_or(0);
+        let mut version = from;
+        while let Some(migration) = self.steps.get(&version) {
+            if let Err(e) = migration(value) {
+                return Err(format!("migration from version {} failed: {}", version, e));
+            }
+            version += 1;
+            value.set_version(&self.field, version);
+        }
+        if version == from {
+            return Ok(None);
+        }
+        info!(path = %path.display(), from, to = version, "config migrated");
+        if let Some(report) = &self.report {
+            report(path, from, version);
+        }
+        Ok(Some((from, version)))
+    }
+
+    /// Read the file, migrate it (writing it back if asked to) and deserialize it.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    pub(crate) fn load<T: serde::de::DeserializeOwned>(
+        &self,
+        watch: &Watch,
+        path: &Path,
+    ) -> Result<T, String> {
+        let mut bytes = Vec::new();
+        if let Err(e) = watch
+            .open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+        {
+            return Err(e.to_string());
+        }
+        let mut value = V::parse(&bytes)?;
+        // never write the decoded (e.g. decrypted) text back, compressed files are compressed again.
+        if self.migrate(path, &mut value)?.is_some() && self.write_back && watch.decoder.is_none() {
+            let written = value.to_bytes().and_then(|bytes| {
+                watch
+                    .recompress(path, bytes)
+                    .and_then(|bytes| watch.env.write(path, &bytes))
+                    .map_err(|e| e.to_string())
+            });
+            if let Err(e) = written {
+                warn!(path = %path.display(), error = %e, "can't write the migrated config back");
+            }
+        }
+        value.deserialize()
+    }
+}
+
+impl<V> fmt::Debug for Migrations<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migrations")
+            .field("field", &self.field)
+            .field("steps", &self.steps.keys().collect::<Vec<_>>())
+            .field("write_back", &self.write_back)
+            .finish()
+    }
+}
+
+#[cfg(all(test, any(feature = "live_ron", feature = "testing")))]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[cfg(feature = "live_ron")]
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Audio {
+        version: u64,
+        volume: f64,
+    }
+
+    #[cfg(feature = "live_ron")]
+    #[test]
+    fn test_migrate_ron() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let report = reported.clone();
+        let migrations = Migrations::<ron::Value>::new()
+            // 0 -> 1: percent to ratio.
+            .step(0, |value| {
+                if let ron::Value::Map(map) = value {
+                    let key = ron::Value::String("volume".to_string());
+                    if let Some(ron::Value::Number(volume)) = map.remove(&key) {
+                        let ratio = ron::Number::from(volume.into_f64() / 100.0);
+                        map.insert(key, ron::Value::Number(ratio));
+                    }
+                }
+                Ok(())
+            })
+            .step(1, |_| Ok(()))
+            .on_migrate(move |_, from, to| report.lock().unwrap().push((from, to)));
+
+        let path = Path::new("audio.ron");
+        let mut value = ron::Value::parse(b"(volume: 50)").unwrap();
+        assert_eq!(migrations.migrate(path, &mut value), Ok(Some((0, 2))));
+        let audio: Audio = value.deserialize().unwrap();
+        assert_eq!(
+            audio,
+            Audio {
+                version: 2,
+                volume: 0.5
+            }
+        );
+
+        let mut value = ron::Value::parse(b"(version: 2, volume: 0.5)").unwrap();
+        assert_eq!(migrations.migrate(path, &mut value), Ok(None));
+        assert_eq!(*reported.lock().unwrap(), vec![(0, 2)]);
+
+        let failing = Migrations::<ron::Value>::new().step(3, |_| Err("no volume".to_string()));
+        let mut value = ron::Value::parse(b"(version: 3)").unwrap();
+        assert!(failing.migrate(path, &mut value).is_err());
+    }
+
+    #[cfg(all(feature = "live_json", feature = "testing"))]
+    #[test]
+    fn test_json_migrations() {
+        use crate::testing::VirtualFs;
+        use serde_json::{json, Value};
+        use std::{path::PathBuf, thread::spawn};
+
+        #[derive(serde::Deserialize)]
+        struct Config {
+            version: u64,
+            title: String,
+        }
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("config.json");
+        fs.write(&file, "{\"version\": 1, \"name\": \"a\"}");
+        let watch = Watch::new().set_fs(&fs);
+        let migrated = Arc::new(Mutex::new(Vec::new()));
+        let report = migrated.clone();
+        let migrations = Arc::new(
+            Migrations::<Value>::new()
+                // 1 -> 2: name renamed to title.
+                .step(1, |value| {
+                    if let Some(config) = value.as_object_mut() {
+                        let name = config.remove("name").unwrap_or_default();
+                        config.insert("title".to_string(), name);
+                    }
+                    Ok(())
+                })
+                .set_write_back(true)
+                .on_migrate(move |_, from, to| report.lock().unwrap().push((from, to))),
+        );
+
+        let mut config = Config {
+            version: 0,
+            title: String::new(),
+        };
+        watch.reinit_de_json_migrated(&mut config, &file, &migrations);
+        assert_eq!((config.version, config.title.as_str()), (2, "a"));
+        assert_eq!(*migrated.lock().unwrap(), vec![(1, 2)]);
+        // written back, upgraded.
+        let written: Value = serde_json::from_slice(&fs.read(&file).unwrap()).unwrap();
+        assert_eq!(written, json!({"version": 2, "title": "a"}));
+
+        let (reload_file, reload_watch) = (file.clone(), watch.clone());
+        let reload_migrations = migrations.clone();
+        let reload = spawn(move || {
+            assert!(reload_watch
+                .de_json_migrated(&mut config, &reload_file, &reload_migrations)
+                .unwrap());
+            config
+        });
+        fs.wait_watchers(1);
+        fs.write(&file, "{\"version\": 1, \"name\": \"b\"}");
+        assert_eq!(reload.join().unwrap().title, "b");
+        assert_eq!(*migrated.lock().unwrap(), vec![(1, 2), (1, 2)]);
+    }
+}
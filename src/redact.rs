@@ -0,0 +1,148 @@
+//! Sensitive values kept out of diagnostics.
+use crate::format::Format;
+use std::io::{self, Read};
+
+/// What sensitive values are replaced with.
+pub const REDACTED: &str = "[redacted]";
+
+/// Sensitive fields of live json/ron values, see Watch::set_redaction.
+///
+/// Errors, schema violations and logs about a file have the text of its sensitive values replaced with REDACTED,
+/// use redacted to print or diff a value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Redaction {
+    paths: Vec<String>,
+    keys: Vec<String>,
+}
+
+impl Redaction {
+    pub fn new() -> Redaction {
+        Self::default()
+    }
+
+    /// Redact the value at the dot separated path, e.g. `database.password` or `tokens.0`.
+    pub fn path(mut self, path: &str) -> Redaction {
+        self.paths.push(path.to_string());
+        self
+    }
+
+    /// Redact the value of every field with this name, at any depth, e.g. `api_key`.
+    pub fn key(mut self, key: &str) -> Redaction {
+        self.keys.push(key.to_string());
+        self
+    }
+
+    /// Replace the sensitive values (and everything inside them) with REDACTED.
+    pub fn redact<V: Format>(&self, value: &mut V) {
+        self.visit(value, &mut |secret| *secret = V::from_text(REDACTED));
+    }
+
+    /// A copy of the value safe to print.
+    pub fn redacted<V: Format>(&self, value: &V) -> V {
+        let mut value = value.clone();
+        self.redact(&mut value);
+        value
+    }
+
+    /// Replace the text of every sensitive value of the document found in the message.
+    pub fn scrub<V: Format>(&self, document: &V, message: &str) -> String {
+        let mut secrets = Vec::new();
+        self.visit(&mut document.clone(), &mut |secret| {
+            texts(secret, &mut secrets)
+        });
+        // longest first, so a secret containing another one is replaced whole.
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        let mut message = message.to_string();
+        for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+            message = message.replace(secret.as_str(), REDACTED);
+            // as quoted in error messages.
+            let escaped = format!("{:?}", secret);
+            message = message.replace(&escaped[1..escaped.len() - 1], REDACTED);
+        }
+        message
+    }
+
+    /// scrub with the sensitive values of the file, the message is kept as is if the file doesn't parse.
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    pub(crate) fn scrub_file<V: Format>(
+        &self,
+        file: io::Result<Box<dyn Read + Send>>,
+        message: &str,
+    ) -> String {
+        let mut bytes = Vec::new();
+        let document = file
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .ok()
+            .and_then(|_| V::parse(&bytes).ok());
+        match document {
+            Some(document) => self.scrub(&document, message),
+            None => message.to_string(),
+        }
+    }
+
+    fn visit<V: Format>(&self, value: &mut V, visit: &mut dyn FnMut(&mut V)) {
+        for path in &self.paths {
+            if let Some(secret) = value.select_mut(path) {
+                visit(secret);
+            }
+        }
+        if !self.keys.is_empty() {
+            self.visit_keys(value, visit);
+        }
+    }
+
+    fn visit_keys<V: Format>(&self, value: &mut V, visit: &mut dyn FnMut(&mut V)) {
+        for (key, child) in value.children_mut() {
+            if key.is_some_and(|key| self.keys.iter().any(|k| k == key)) {
+                visit(child);
+            } else {
+                self.visit_keys(child, visit);
+            }
+        }
+    }
+}
+
+fn texts<V: Format>(value: &mut V, texts_of: &mut Vec<String>) {
+    match value.text() {
+        Some(text) => texts_of.push(text),
+        None => {
+            for (_, child) in value.children_mut() {
+                texts(child, texts_of);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "live_json"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redaction() {
+        let redaction = Redaction::new().path("database.password").key("api_key");
+        let config = json!({
+            "database": {"user": "admin", "password": "hunter2\"x"},
+            "services": [{"name": "maps", "api_key": {"id": 7, "secret": "sk-123"}}],
+        });
+        assert_eq!(
+            redaction.redacted(&config),
+            json!({
+                "database": {"user": "admin", "password": REDACTED},
+                "services": [{"name": "maps", "api_key": REDACTED}],
+            })
+        );
+
+        let error = serde_json::from_value::<u32>(config["database"]["password"].clone())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("hunter2"));
+        let scrubbed = redaction.scrub(&config, &error);
+        assert!(!scrubbed.contains("hunter2"), "{}", scrubbed);
+        assert!(scrubbed.contains(REDACTED));
+        assert_eq!(
+            redaction.scrub(&config, "maps: sk-123 (admin)"),
+            "maps: [redacted] (admin)"
+        );
+    }
+}
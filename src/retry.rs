@@ -0,0 +1,153 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+
+/// How the delay grows between retries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backoff {
+    /// Same delay on every attempt.
+    Fixed,
+    /// interval, 2 * interval, 3 * interval...
+    Linear,
+    /// interval, 2 * interval, 4 * interval...
+    Exponential,
+}
+
+/// Longest delay between two retries unless set otherwise with RetryPolicy::max_delay.
+const MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+/// Retry policy used while a watched file is missing (renamed/removed), before giving up with NotFound.
+///
+/// By default the watcher retries 8 times with a fixed delay of timeout / 7.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    backoff: Backoff,
+    interval: Duration,
+    max_attempts: usize,
+    max_delay: Duration,
+    jitter: f32,
+}
+
+impl RetryPolicy {
+    pub fn new(backoff: Backoff, interval: Duration) -> RetryPolicy {
+        Self {
+            backoff,
+            interval,
+            max_attempts: 8,
+            max_delay: MAX_DELAY,
+            jitter: 0.0,
+        }
+    }
+
+    /// Fixed delay between retries, suits local disks.
+    pub fn fixed(interval: Duration) -> RetryPolicy {
+        Self::new(Backoff::Fixed, interval)
+    }
+
+    /// Linearly growing delay between retries.
+    pub fn linear(interval: Duration) -> RetryPolicy {
+        Self::new(Backoff::Linear, interval)
+    }
+
+    /// Doubling delay between retries, suits network shares and slow CI disks.
+    pub fn exponential(interval: Duration) -> RetryPolicy {
+        Self::new(Backoff::Exponential, interval)
+    }
+
+    /// Number of attempts before the file is considered gone, at least 1.
+    pub fn max_attempts(mut self, max_attempts: usize) -> RetryPolicy {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Cap of the delay between retries (1 hour by default), jitter still applies on top of it.
+    pub fn max_delay(mut self, max_delay: Duration) -> RetryPolicy {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Randomize each delay by up to ± ratio of itself (clamped to 0.0..=1.0),
+    /// so many watchers on the same share don't retry in lockstep.
+    pub fn jitter(mut self, ratio: f32) -> RetryPolicy {
+        self.jitter = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Delay before the given attempt (starting from 0).
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let delay = match self.backoff {
+            Backoff::Fixed => self.interval,
+            Backoff::Linear => self
+                .interval
+                .checked_mul(attempt as u32 + 1)
+                .unwrap_or(Duration::MAX),
+            Backoff::Exponential => self
+                .interval
+                .checked_mul(1 << attempt.min(31))
+                .unwrap_or(Duration::MAX),
+        }
+        .min(self.max_delay);
+        if self.jitter > 0.0 {
+            // 1.0 ± jitter
+            let factor = 1.0 + self.jitter as f64 * (random_unit() * 2.0 - 1.0);
+            Duration::try_from_secs_f64(delay.as_secs_f64() * factor).unwrap_or(delay)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Random number in 0.0..1.0, seeded by the std random hasher keys (no extra dependency needed).
+#[inline]
+fn random_unit() -> f64 {
+    let hasher = RandomState::new().build_hasher();
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let interval = Duration::from_millis(100);
+        let fixed = RetryPolicy::fixed(interval);
+        let linear = RetryPolicy::linear(interval);
+        let exponential = RetryPolicy::exponential(interval).max_attempts(0);
+        assert_eq!(fixed.delay(3), interval);
+        assert_eq!(linear.delay(3), Duration::from_millis(400));
+        assert_eq!(exponential.delay(3), Duration::from_millis(800));
+        assert_eq!(exponential.attempts(), 1);
+
+        let jittered = RetryPolicy::fixed(interval).jitter(0.5);
+        for attempt in 0..32 {
+            let delay = jittered.delay(attempt);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+    }
+    #[test]
+    fn test_large_delays() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        for policy in [
+            RetryPolicy::exponential(day).max_attempts(100).jitter(1.0),
+            RetryPolicy::linear(Duration::MAX).jitter(0.5),
+            RetryPolicy::fixed(Duration::MAX)
+                .max_delay(Duration::MAX)
+                .jitter(1.0),
+        ] {
+            for attempt in 0..100 {
+                policy.delay(attempt);
+            }
+        }
+        let capped = RetryPolicy::exponential(day).max_delay(day * 2).jitter(0.5);
+        assert!(capped.delay(99) <= day * 3);
+        let now = std::time::Instant::now();
+        assert!(crate::env::deadline(now, Duration::MAX) > now);
+    }
+}
@@ -0,0 +1,156 @@
+//! JSON Schema validation of live json values.
+use serde_json::Value;
+use std::fmt;
+
+/// Where and why a value doesn't match its schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, e.g. `/window/width`.
+    pub instance_path: String,
+    /// JSON pointer to the schema keyword that rejected it, e.g. `/properties/window/properties/width/minimum`.
+    pub schema_path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = if self.instance_path.is_empty() {
+            "/"
+        } else {
+            &self.instance_path
+        };
+        write!(f, "{}: {} ({})", at, self.message, self.schema_path)
+    }
+}
+
+impl SchemaViolation {
+    fn new(e: jsonschema::ValidationError) -> SchemaViolation {
+        Self {
+            instance_path: e.instance_path.to_string(),
+            schema_path: e.schema_path.to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+/// A compiled JSON Schema, the draft (7, 2019-09, 2020-12...) is picked from `$schema`, 2020-12 if there's none.
+pub struct Schema {
+    validator: jsonschema::Validator,
+}
+
+impl Schema {
+    /// Compile the schema, Err if it isn't a valid schema itself.
+    pub fn new(schema: &Value) -> Result<Schema, SchemaViolation> {
+        match jsonschema::validator_for(schema) {
+            Ok(validator) => Ok(Self { validator }),
+            Err(e) => Err(SchemaViolation::new(e)),
+        }
+    }
+
+    /// Every violation of the value, empty if it's valid.
+    pub fn validate(&self, value: &Value) -> Vec<SchemaViolation> {
+        self.validator
+            .iter_errors(value)
+            .map(SchemaViolation::new)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_schema_violations() {
+        let schema = Schema::new(&json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {
+                "window": {
+                    "type": "object",
+                    "properties": { "width": { "type": "integer", "minimum": 320 } }
+                },
+                "title": { "type": "string" }
+            },
+            "required": ["window"]
+        }))
+        .unwrap();
+        assert!(schema
+            .validate(&json!({"window": {"width": 640}}))
+            .is_empty());
+
+        let mut violations = schema.validate(&json!({"window": {"width": 100}, "title": 7}));
+        violations.sort_by(|a, b| a.instance_path.cmp(&b.instance_path));
+        let paths: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.instance_path.as_str(), v.schema_path.as_str()))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                ("/title", "/properties/title/type"),
+                (
+                    "/window/width",
+                    "/properties/window/properties/width/minimum"
+                )
+            ]
+        );
+        assert_eq!(
+            schema.validate(&json!({}))[0].to_string(),
+            "/: \"window\" is a required property (/required)"
+        );
+
+        assert!(Schema::new(&json!({"type": 12})).is_err());
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn test_json_schema() {
+        use crate::{testing::VirtualFs, Watch};
+        use serde_json::Value;
+        use std::{path::PathBuf, sync::Arc, thread::spawn};
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("config.json");
+        fs.write(&file, "{\"width\": 640}");
+        let watch = Watch::new().set_fs(&fs);
+        let schema = Arc::new(
+            Schema::new(&json!({
+                "properties": { "width": { "type": "integer", "minimum": 320 } }
+            }))
+            .unwrap(),
+        );
+
+        let (mut val, mut violations) = (Value::Null, Vec::new());
+        watch.json_val_init_with_schema(&file, &mut val, &schema, &mut violations);
+        assert_eq!(val, json!({"width": 640}));
+
+        // the watchers of earlier reloads keep running.
+        let reload = |content: &'static str, mut val: Value, watchers: usize| {
+            let (reload_file, reload_watch) = (file.clone(), watch.clone());
+            let reload_schema = schema.clone();
+            let reload = spawn(move || {
+                let mut violations = Vec::new();
+                assert!(reload_watch
+                    .json_val_with_schema(&reload_file, &mut val, &reload_schema, &mut violations)
+                    .unwrap());
+                (val, violations)
+            });
+            fs.wait_watchers(watchers);
+            fs.write(&file, content);
+            reload.join().unwrap()
+        };
+
+        // rejected, the last valid value is kept.
+        let (val, violations) = reload("{\"width\": 100}", val, 1);
+        assert_eq!(val, json!({"width": 640}));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].instance_path, "/width");
+        assert_eq!(violations[0].schema_path, "/properties/width/minimum");
+
+        let (val, violations) = reload("{\"width\": 800}", val, 2);
+        assert_eq!(val, json!({"width": 800}));
+        assert!(violations.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Sections of one watched json/ron file, each deserialized into its own type.
+use crate::{format::Format, live::LiveFiles, Watch, FALSE, TRUE};
+use notify::Result;
+use std::{
+    any::Any,
+    fmt,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// Untyped identity of a section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SectionId(usize);
+
+typed_handle! {
+    /// Typed handle of a section, get its value with Sections::get.
+    pub struct Section<T>(SectionId);
+}
+
+type Deserialize<V> = Box<dyn Fn(V) -> std::result::Result<Box<dyn Any>, String>>;
+
+struct Slot<V> {
+    selector: String,
+    deserialize: Deserialize<V>,
+    /// the section as of the last update, compared to tell if it changed.
+    last: Option<V>,
+    value: Option<Box<dyn Any>>,
+    error: Option<String>,
+}
+
+/// One watched json (serde_json::Value) or ron (ron::Value) file split into sections, see Watch::sections.
+///
+/// The file is read and parsed once per change, then each section is only deserialized if its value changed:
+/// formatting or edits elsewhere in the file don't touch it, and a section that fails doesn't block the others.
+pub struct Sections<V: Format> {
+    // read (decoded, decompressed) and redacted as this watch does.
+    watch: Watch,
+    path: PathBuf,
+    files: LiveFiles,
+    document: Option<V>,
+    error: Option<String>,
+    slots: Vec<Slot<V>>,
+}
+
+impl<V: Format> Sections<V> {
+    pub(crate) fn new(watch: &Watch, path: &Path) -> Result<Sections<V>> {
+        if !watch.env.is_file(path) {
+            return Err(notify::Error::path_not_found().add_path(path.to_path_buf()));
+        }
+        let files = LiveFiles::new(watch);
+        let path = watch.env.canonicalize(path);
+        files.add(&path)?;
+        let mut sections = Self {
+            watch: watch.clone(),
+            path,
+            files,
+            document: None,
+            error: None,
+            slots: Vec::new(),
+        };
+        sections.parse();
+        Ok(sections)
+    }
+
+    /// Bind the section at the selector (dot separated keys or sequence indexes, "" for the whole file) to T,
+    /// deserialized right away if the file parsed.
+    pub fn bind<T: serde::de::DeserializeOwned + 'static>(&mut self, selector: &str) -> Section<T> {
+        self.slots.push(Slot {
+            selector: selector.to_string(),
+            deserialize: Box::new(|section| match section.deserialize::<T>() {
+                Ok(value) => Ok(Box::new(value)),
+                Err(e) => Err(e),
+            }),
+            last: None,
+            value: None,
+            error: None,
+        });
+        let id = self.slots.len() - 1;
+        if let Some(document) = self.document.take() {
+            self.deserialize(id, &document);
+            self.document = Some(document);
+        }
+        Section::new(SectionId(id))
+    }
+
+    /// The current value of the section, the last good one if it failed. None if it never had one.
+    pub fn get<T: 'static>(&self, section: &Section<T>) -> Option<&T> {
+        self.slots.get(section.id.0)?.value.as_ref()?.downcast_ref()
+    }
+
+    /// Why the section has no up to date value: missing from the file or not deserializable into its type.
+    pub fn error(&self, id: SectionId) -> Option<&str> {
+        self.slots.get(id.0)?.error.as_deref()
+    }
+
+    pub fn selector(&self, id: SectionId) -> Option<&str> {
+        self.slots.get(id.0).map(|slot| slot.selector.as_str())
+    }
+
+    /// Why the file itself couldn't be read or parsed, every section keeps its last value meanwhile.
+    pub fn file_error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-parse the file if it changed since the last update, never blocks.
+    ///
+    /// returns the sections that got a new value, in the order they were bound.
+    pub fn update(&mut self) -> Vec<SectionId> {
+        // the only file watched, any change is to it.
+        if !self.files.changed().is_empty() {
+            self.parse()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn parse(&mut self) -> Vec<SectionId> {
+        let mut bytes = Vec::new();
+        let parsed = match self
+            .watch
+            .open(&self.path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+        {
+            Ok(_) => V::parse(&bytes),
+            Err(e) => Err(e.to_string()),
+        };
+        let document = match parsed {
+            Ok(document) => document,
+            Err(error) => {
+                // scrubbed with the last good document, the broken one can't tell what's sensitive.
+                let error = match (&self.watch.redaction, &self.document) {
+                    (Some(redaction), Some(document)) => redaction.scrub(document, &error),
+                    _ => error,
+                };
+                warn!(path = %self.path.display(), error = %error, "file failed to parse, sections kept");
+                self.error = Some(error);
+                return Vec::new();
+            }
+        };
+        self.error = None;
+        let updated = (0..self.slots.len())
+            .filter(|id| self.deserialize(*id, &document))
+            .map(SectionId)
+            .collect();
+        self.document = Some(document);
+        updated
+    }
+
+    fn deserialize(&mut self, id: usize, document: &V) -> bool {
+        let slot = &mut self.slots[id];
+        let section = match document.select(&slot.selector) {
+            Some(section) => section,
+            None => {
+                slot.last = None;
+                slot.error = Some(format!("no section at `{}`", slot.selector));
+                return FALSE;
+            }
+        };
+        if slot.last.as_ref().is_some_and(|last| last.same(section)) {
+            return FALSE;
+        }
+        slot.last = Some(section.clone());
+        let section = section.clone();
+        match (slot.deserialize)(section) {
+            Ok(value) => {
+                slot.value = Some(value);
+                slot.error = None;
+                TRUE
+            }
+            Err(error) => {
+                let error = match &self.watch.redaction {
+                    Some(redaction) => redaction.scrub(document, &error),
+                    None => error,
+                };
+                warn!(path = %self.path.display(), section = %slot.selector, error = %error, "section failed to deserialize, last value kept");
+                slot.error = Some(error);
+                FALSE
+            }
+        }
+    }
+}
+
+impl<V: Format> fmt::Debug for Sections<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sections")
+            .field("path", &self.path)
+            .field(
+                "selectors",
+                &self
+                    .slots
+                    .iter()
+                    .map(|slot| &slot.selector)
+                    .collect::<Vec<_>>(),
+            )
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "live_ron", feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::{testing::VirtualFs, Redaction};
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Audio {
+        volume: f64,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Physics {
+        gravity: f64,
+    }
+
+    #[test]
+    fn test_sections() {
+        let fs = VirtualFs::new();
+        let path = PathBuf::from("config/game.ron");
+        fs.write(&path, "(audio: (volume: 0.5), physics: (gravity: 9.8))");
+
+        let mut sections = Watch::new()
+            .set_fs(&fs)
+            .set_redaction(Redaction::new().key("gravity"))
+            .sections::<ron::Value>(&path)
+            .unwrap();
+        let audio = sections.bind::<Audio>("audio");
+        let physics = sections.bind::<Physics>("physics");
+        let missing = sections.bind::<Physics>("world.physics");
+        assert_eq!(sections.get(&audio), Some(&Audio { volume: 0.5 }));
+        assert_eq!(sections.get(&physics), Some(&Physics { gravity: 9.8 }));
+        assert_eq!(sections.get(&missing), None);
+        assert!(sections.error(missing.id()).is_some());
+        fs.wait_watchers(1);
+
+        // reformatted audio, broken physics.
+        fs.write(
+            &path,
+            "(\n  audio: (volume: 0.5),\n  physics: (gravity: \"down\"),\n)",
+        );
+        assert!(sections.update().is_empty());
+        let error = sections.error(physics.id()).unwrap();
+        assert!(
+            !error.contains("down") && error.contains(crate::REDACTED),
+            "{}",
+            error
+        );
+        assert_eq!(sections.get(&physics), Some(&Physics { gravity: 9.8 }));
+        assert_eq!(sections.get(&audio), Some(&Audio { volume: 0.5 }));
+
+        fs.write(
+            &path,
+            "(audio: (volume: 0.2), physics: (gravity: \"down\"))",
+        );
+        assert_eq!(sections.update(), vec![audio.id()]);
+        assert_eq!(sections.get(&audio), Some(&Audio { volume: 0.2 }));
+        assert!(sections.error(physics.id()).is_some());
+
+        fs.write(&path, "(audio: (volume: 0.2), physics: (gravity:");
+        assert!(sections.update().is_empty());
+        assert!(sections.file_error().is_some());
+        assert_eq!(sections.get(&audio), Some(&Audio { volume: 0.2 }));
+
+        // audio didn't change, it isn't deserialized again.
+        fs.write(&path, "(audio: (volume: 0.2), physics: (gravity: 1.6))");
+        assert_eq!(sections.update(), vec![physics.id()]);
+        assert_eq!(sections.get(&physics), Some(&Physics { gravity: 1.6 }));
+        assert_eq!(sections.file_error(), None);
+    }
+}
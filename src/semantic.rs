@@ -0,0 +1,119 @@
+//! Semantic comparison of json/ron files, formatting only changes aren't changes.
+use crate::{format::Format, Watch, TRUE};
+use std::{
+    io::{self, Read},
+    path::Path,
+};
+
+/// Last parsed value of a json or ron file, None if it didn't parse.
+pub(crate) enum Semantic {
+    #[cfg(feature = "live_json")]
+    Json(Option<serde_json::Value>),
+    #[cfg(feature = "live_ron")]
+    Ron(Option<ron::Value>),
+}
+
+impl Semantic {
+    /// None unless the watch compares semantically and the file is json or ron (by extension, compressed or not).
+    pub(crate) fn new(file: &Path, opts: &Watch) -> Option<Semantic> {
+        if !opts.semantic {
+            return None;
+        }
+        let name = file.file_name()?.to_string_lossy().to_lowercase();
+        let name = [".gz", ".zst", ".zstd"]
+            .iter()
+            .find_map(|extension| name.strip_suffix(extension))
+            .unwrap_or(&name);
+        let mut semantic = match Path::new(name).extension()?.to_str()? {
+            #[cfg(feature = "live_json")]
+            "json" => Semantic::Json(None),
+            #[cfg(feature = "live_ron")]
+            "ron" => Semantic::Ron(None),
+            _ => {
+                debug!("neither json nor ron, compared byte for byte");
+                return None;
+            }
+        };
+        semantic.changed(opts.open(file));
+        Some(semantic)
+    }
+
+    /// Parse the file again, true if its value differs from the last one or either of them doesn't parse.
+    pub(crate) fn changed(&mut self, file: io::Result<Box<dyn Read + Send>>) -> bool {
+        let mut bytes = Vec::new();
+        let bytes = match file.and_then(|mut file| file.read_to_end(&mut bytes)) {
+            Ok(_) => Some(&bytes[..]),
+            Err(_) => None,
+        };
+        match self {
+            #[cfg(feature = "live_json")]
+            Semantic::Json(last) => differs(last, bytes),
+            #[cfg(feature = "live_ron")]
+            Semantic::Ron(last) => differs(last, bytes),
+        }
+    }
+}
+
+fn differs<V: Format>(last: &mut Option<V>, bytes: Option<&[u8]>) -> bool {
+    let value = bytes.and_then(|bytes| V::parse(bytes).ok());
+    let differs = match (&value, &*last) {
+        (Some(value), Some(last)) => !value.same(last),
+        // a broken file is reported, and so is the first good one after it.
+        _ => TRUE,
+    };
+    if !differs {
+        debug!("formatting only, suppressed");
+    }
+    *last = value;
+    differs
+}
+
+#[cfg(all(test, feature = "live_json", feature = "live_ron", feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::{testing::VirtualFs, Fstate};
+    use cfg_if::cfg_if;
+    use std::path::PathBuf;
+    cfg_if! {
+       if #[cfg(feature = "crossbeam_channel")] {
+           use crossbeam_channel::unbounded as channel;
+       } else if #[cfg(feature = "flume_channel")] {
+           use flume::unbounded as channel;
+       } else {
+           use std::sync::mpsc::channel;
+       }
+    }
+
+    #[test]
+    fn test_semantic() {
+        let fs = VirtualFs::new();
+        let json = PathBuf::from("config.json");
+        let ron = PathBuf::from("config.ron");
+        fs.write(&json, "{\"a\": 1, \"b\": [1, 2]}");
+        fs.write(&ron, "(a: 1, b: [1, 2])");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new().set_fs(&fs).set_semantic(TRUE);
+        assert!(watch.single_file(&json, tx.clone()).unwrap());
+        assert!(watch.single_file(&ron, tx).unwrap());
+        fs.wait_watchers(2);
+
+        // reformatted, reordered, commented.
+        fs.write(&json, "{\n  \"b\": [1, 2],\n  \"a\": 1\n}\n");
+        fs.write(&ron, "// tuned\n(\n    b: [1, 2],\n    a: 1,\n)");
+        assert!(rx.try_recv().is_err());
+        assert_eq!(watch.stats().suppressed, 2);
+
+        fs.write(&json, "{\"b\": [2, 1], \"a\": 1}");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        fs.write(&ron, "(a: 1, b: [1, 2], c: 3)");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+
+        // a broken file is a change, and so is the first good one after it, even if it's the same as before.
+        fs.write(&json, "{\"b\": [2, 1],");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        fs.write(&json, "{\"a\": 1, \"b\": [2, 1]}");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{watch, Fstate, Sender, Watch, FALSE, TRUE};
+use notify::Result;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering::Relaxed},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::spawn,
+};
+
+struct Entry<T> {
+    tag: T,
+    // stop flag, also raised by the watcher itself once it terminated (e.g. NotFound).
+    stop: Arc<AtomicBool>,
+}
+
+/// Raises the stop flag of a WatchSet watcher when its thread terminates, even by panicking,
+/// so the set prunes it and the path can be added again.
+pub(crate) struct Stopped<'a>(pub(crate) Option<&'a AtomicBool>);
+
+impl Drop for Stopped<'_> {
+    fn drop(&mut self) {
+        if let Some(stop) = self.0 {
+            stop.store(TRUE, Relaxed);
+        }
+    }
+}
+
+/// A live set of watched files, paths can be added and removed while it runs.
+///
+/// Every file is registered with a tag (an asset id, a reload kind...) which comes back with each of its states,
+/// WatchSet<PathBuf> (the default) tags files with their path through add.
+/// Paths are canonicalised, so equivalent forms (`./a.ron`, `dir/../a.ron`, symlinked directories) are the same entry.
+///
+/// Every watcher reports to the same channel. Clones share the same set, so it can be handed to other threads.
+pub struct WatchSet<T = PathBuf> {
+    watch: Watch,
+    tx: Sender<Fstate<T>>,
+    paths: Arc<Mutex<HashMap<PathBuf, Entry<T>>>>,
+}
+
+impl<T> Clone for WatchSet<T> {
+    fn clone(&self) -> Self {
+        Self {
+            watch: self.watch.clone(),
+            tx: self.tx.clone(),
+            paths: self.paths.clone(),
+        }
+    }
+}
+
+impl<T: Clone + Send + 'static> WatchSet<T> {
+    pub(crate) fn new(watch: Watch, tx: Sender<Fstate<T>>) -> WatchSet<T> {
+        Self {
+            watch,
+            tx,
+            paths: Arc::default(),
+        }
+    }
+
+    /// Running watchers only, the ones that terminated on their own are pruned.
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Entry<T>>> {
+        let mut paths = self.paths.lock().unwrap_or_else(|e| e.into_inner());
+        paths.retain(|_, entry| !entry.stop.load(Relaxed));
+        paths
+    }
+
+    #[inline]
+    fn key(&self, path: &Path) -> PathBuf {
+        self.watch.env.canonicalize(path)
+    }
+
+    /// Start watching the file with the given tag, false if it doesn't exist (or isn't a file) or is already watched.
+    pub fn insert<P: AsRef<Path>>(&self, path: P, tag: T) -> Result<bool> {
+        let path = path.as_ref();
+        let key = self.key(path);
+        let mut paths = self.lock();
+        if paths.contains_key(&key) || !self.watch.env.is_file(path) {
+            return Ok(FALSE);
+        }
+        let stop = Arc::new(AtomicBool::new(FALSE));
+        let entry = Entry {
+            tag: tag.clone(),
+            stop: stop.clone(),
+        };
+        paths.insert(key, entry);
+        let (file, tx) = (path.to_path_buf(), self.tx.clone());
+        let mut opts = self.watch.clone();
+        opts.stop = Some(stop);
+        self.watch.env.enter();
+        spawn(move || {
+            watch(file, tag, tx, opts).expect("error occured while spawning watcher.");
+        });
+        Ok(TRUE)
+    }
+
+    /// Stop watching the file, no more state is sent for it. Returns its tag, None if it wasn't watched.
+    ///
+    /// the watcher terminates quietly the next time it wakes up (within timeout / 7, or the retry delay).
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> Option<T> {
+        let key = self.key(path.as_ref());
+        self.lock().remove(&key).map(|entry| {
+            entry.stop.store(TRUE, Relaxed);
+            entry.tag
+        })
+    }
+
+    /// Stop watching every file.
+    pub fn clear(&self) {
+        for (_, entry) in self.lock().drain() {
+            entry.stop.store(TRUE, Relaxed);
+        }
+    }
+
+    /// The tag the file was registered with.
+    pub fn tag<P: AsRef<Path>>(&self, path: P) -> Option<T> {
+        let key = self.key(path.as_ref());
+        self.lock().get(&key).map(|entry| entry.tag.clone())
+    }
+
+    pub fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        let key = self.key(path.as_ref());
+        self.lock().contains_key(&key)
+    }
+
+    /// The watched files (canonical paths), in no particular order.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.lock().keys().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+}
+
+impl WatchSet<PathBuf> {
+    /// Start watching the file, tagged with its path as given. false if it doesn't exist (or isn't a file) or is already watched.
+    pub fn add<P: AsRef<Path>>(&self, path: P) -> Result<bool> {
+        let path = path.as_ref();
+        self.insert(path, path.to_path_buf())
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::testing::VirtualFs;
+    use cfg_if::cfg_if;
+    use std::time::Duration;
+    cfg_if! {
+       if #[cfg(feature = "crossbeam_channel")] {
+           use crossbeam_channel::unbounded as channel;
+       } else if #[cfg(feature = "flume_channel")] {
+           use flume::unbounded as channel;
+       } else {
+           use std::sync::mpsc::channel;
+       }
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(700);
+
+    #[test]
+    fn test_watch_set() {
+        let fs = VirtualFs::new();
+        let (a, b) = (PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs"));
+        fs.write(&a, "a");
+        fs.write(&b, "b");
+
+        let (tx, rx) = channel();
+        let set = Watch::new().set_fs(&fs).set_timeout(TIMEOUT).watch_set(tx);
+        assert!(set.add(&a).unwrap() && set.add(&b).unwrap());
+        assert!(!set.add(&a).unwrap());
+        assert!(!set.add("src/c.rs").unwrap());
+        fs.wait_watchers(2);
+        assert_eq!(set.len(), 2);
+
+        fs.write(&b, "bb");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(path)) if path == b));
+
+        // closed in the editor: quiet from now on, the watcher is gone on its next wake up.
+        assert_eq!(set.remove(&b), Some(b.clone()));
+        assert!(!set.contains(&b));
+        fs.write(&b, "bbb");
+        fs.advance(TIMEOUT / 7);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(fs.watchers(), 1);
+
+        // a watcher that terminated on its own leaves the set too.
+        fs.remove(&a);
+        fs.advance(TIMEOUT + TIMEOUT / 7);
+        assert!(matches!(rx.try_recv(), Ok(Fstate::NotFound(path)) if path == a));
+        assert!(set.is_empty());
+
+        fs.write(&a, "a");
+        assert!(set.add(&a).unwrap());
+        assert_eq!(set.paths(), [a]);
+    }
+
+    #[test]
+    fn test_watch_set_tags() {
+        #[derive(Clone, Debug, PartialEq)]
+        enum Asset {
+            Shader(u32),
+            Level(u32),
+        }
+
+        let fs = VirtualFs::new();
+        fs.write("assets/lit.wgsl", "fn main() {}");
+        fs.write("assets/levels/1.ron", "()");
+
+        let (tx, rx) = channel();
+        let set = Watch::new().set_fs(&fs).watch_set(tx);
+        assert!(set.insert("assets/lit.wgsl", Asset::Shader(7)).unwrap());
+        assert!(set
+            .insert("./assets/levels/1.ron", Asset::Level(1))
+            .unwrap());
+        // the same file in another form.
+        assert!(!set
+            .insert("assets/levels/../lit.wgsl", Asset::Shader(8))
+            .unwrap());
+        fs.wait_watchers(2);
+        assert_eq!(set.tag("assets/./lit.wgsl"), Some(Asset::Shader(7)));
+
+        fs.write("assets/levels/1.ron", "(spawn: (0, 0))");
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Fstate::Changed(Asset::Level(1)))
+        ));
+        assert_eq!(set.remove("assets/lit.wgsl"), Some(Asset::Shader(7)));
+        assert_eq!(set.paths(), [PathBuf::from("assets/levels/1.ron")]);
+    }
+}
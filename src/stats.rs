@@ -0,0 +1,111 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed},
+    time::Duration,
+};
+
+/// Snapshot of what the watchers spawned from a Watch (and its clones) have been doing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Files currently being watched.
+    pub watched_files: usize,
+    /// Raw notify events received (for the watched files' directories).
+    pub events: u64,
+    /// Changes reported with Fstate::Changed.
+    pub changes: u64,
+    /// Writes that left the content identical, not reported.
+    pub suppressed: u64,
+    /// Retries while a watched file was missing.
+    pub retries: u64,
+    /// Time spent reading the watched files.
+    pub read_time: Duration,
+}
+
+/// Shared counters behind Stats, also forwarded to the metrics facade with the `metrics` feature.
+#[derive(Default)]
+pub(crate) struct Counters {
+    watched_files: AtomicUsize,
+    events: AtomicU64,
+    changes: AtomicU64,
+    suppressed: AtomicU64,
+    retries: AtomicU64,
+    read_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            watched_files: self.watched_files.load(Relaxed),
+            events: self.events.load(Relaxed),
+            changes: self.changes.load(Relaxed),
+            suppressed: self.suppressed.load(Relaxed),
+            retries: self.retries.load(Relaxed),
+            read_time: Duration::from_nanos(self.read_nanos.load(Relaxed)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn watcher_started(&self) {
+        self.watched_files.fetch_add(1, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("quadoculars_watched_files").increment(1.0);
+    }
+
+    #[inline]
+    pub(crate) fn watcher_stopped(&self) {
+        self.watched_files.fetch_sub(1, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("quadoculars_watched_files").decrement(1.0);
+    }
+
+    #[inline]
+    pub(crate) fn event(&self) {
+        self.events.fetch_add(1, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("quadoculars_events_total").increment(1);
+    }
+
+    #[inline]
+    pub(crate) fn changed(&self) {
+        self.changes.fetch_add(1, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("quadoculars_changes_total").increment(1);
+    }
+
+    #[inline]
+    pub(crate) fn suppressed(&self) {
+        self.suppressed.fetch_add(1, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("quadoculars_suppressed_total").increment(1);
+    }
+
+    #[inline]
+    pub(crate) fn retry(&self) {
+        self.retries.fetch_add(1, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::counter!("quadoculars_retries_total").increment(1);
+    }
+
+    #[inline]
+    pub(crate) fn read(&self, elapsed: Duration) {
+        self.read_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Relaxed);
+        #[cfg(feature = "metrics")]
+        metrics::histogram!("quadoculars_read_seconds").record(elapsed.as_secs_f64());
+    }
+}
+
+/// Keeps watched_files balanced when the watcher thread terminates, even by panicking.
+pub(crate) struct Watched<'a>(pub(crate) &'a Counters);
+
+impl<'a> Watched<'a> {
+    pub(crate) fn new(counters: &'a Counters) -> Watched<'a> {
+        counters.watcher_started();
+        Self(counters)
+    }
+}
+
+impl Drop for Watched<'_> {
+    fn drop(&mut self) {
+        self.0.watcher_stopped();
+    }
+}
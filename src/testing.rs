@@ -0,0 +1,770 @@
+//! Deterministic test harness: an in-memory filesystem with injectable events and a manual clock.
+//!
+//! Run a Watch (or LiveJson/LiveRon through their `_with` methods) against a VirtualFs with Watch::set_fs,
+//! then drive it from the test: write/rename/remove files and advance the clock.
+//! Every call returns once all watchers have processed it, so no real sleeps are needed.
+//!
+//! Use unbounded channels for the watchers, a watcher blocked on a full channel never settles.
+//!
+//! ```
+//! use quadoculars::{testing::VirtualFs, Fstate, Watch};
+//! use std::{path::PathBuf, time::Duration};
+//! # #[cfg(feature = "crossbeam_channel")]
+//! # use crossbeam_channel::unbounded as channel;
+//! # #[cfg(all(feature = "flume_channel", not(feature = "crossbeam_channel")))]
+//! # use flume::unbounded as channel;
+//! # #[cfg(not(any(feature = "crossbeam_channel", feature = "flume_channel")))]
+//! # use std::sync::mpsc::channel;
+//!
+//! let fs = VirtualFs::new();
+//! let file = PathBuf::from("assets/config.json");
+//! fs.write(&file, "{}");
+//!
+//! let (tx, rx) = channel();
+//! let watch = Watch::new().set_fs(&fs).set_timeout(Duration::from_millis(700));
+//! assert!(watch.single_file(&file, tx).unwrap());
+//! fs.wait_watchers(1);
+//!
+//! fs.write(&file, "{\"a\": 1}");
+//! assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+//!
+//! // gone for good: 8 retries, 100ms apart.
+//! fs.remove(&file);
+//! fs.advance(Duration::from_millis(700));
+//! assert!(rx.try_recv().is_err());
+//! fs.advance(Duration::from_millis(100));
+//! assert!(matches!(rx.try_recv(), Ok(Fstate::NotFound(_))));
+//! ```
+
+use crate::{
+    env::{normalize, Env, Stamp, Subscription},
+    Fmeta, FALSE, TRUE,
+};
+use notify::{
+    event::{CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind, RenameMode},
+    Event, EventKind, Result,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+/// In-memory filesystem and clock, clones share the same state.
+///
+/// Paths are normalized lexically (`a/./b` and `a/c/../b` are `a/b`, an empty path is `.`), there is no current
+/// directory: `config.json` and `/config.json` are different files.
+#[derive(Clone)]
+pub struct VirtualFs {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    base: Instant,
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+struct VFile {
+    data: Vec<u8>,
+    modified: Duration,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+}
+
+struct Dir {
+    paths: Vec<PathBuf>,
+    queue: VecDeque<Event>,
+}
+
+/// A watcher thread blocked until the deadline, or until an event shows up for its subscription.
+struct Parked {
+    deadline: Instant,
+    subscription: Option<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    elapsed: Duration,
+    files: HashMap<PathBuf, VFile>,
+    muted: bool,
+    subscriptions: HashMap<usize, Dir>,
+    parked: HashMap<usize, Parked>,
+    next_id: usize,
+    // running watcher threads, and all of them ever spawned.
+    watchers: usize,
+    spawned: usize,
+}
+
+/// Files are stored under their lexically normalized path, so `./a.ron` and `dir/../a.ron` are the same file.
+#[inline]
+fn key(path: &Path) -> PathBuf {
+    let path = normalize(path);
+    if path.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        path
+    }
+}
+
+#[inline]
+fn dir_of(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if dir != Path::new("") => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+impl State {
+    #[inline]
+    fn is_ready(&self, parked: &Parked, now: Instant) -> bool {
+        parked.deadline <= now
+            || parked
+                .subscription
+                .and_then(|id| self.subscriptions.get(&id))
+                .is_some_and(|dir| !dir.queue.is_empty())
+    }
+
+    fn write(&mut self, path: PathBuf, data: &[u8]) {
+        let modified = self.elapsed;
+        let created = match self.files.get_mut(&path) {
+            Some(file) => {
+                file.data = data.to_vec();
+                file.modified = modified;
+                FALSE
+            }
+            None => {
+                let file = VFile {
+                    data: data.to_vec(),
+                    modified,
+                    mode: 0o644,
+                    uid: 1000,
+                    gid: 1000,
+                };
+                self.files.insert(path.clone(), file);
+                TRUE
+            }
+        };
+        if created {
+            self.dispatch(Event::new(EventKind::Create(CreateKind::File)).add_path(path.clone()));
+        }
+        self.dispatch(
+            Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content))).add_path(path),
+        );
+    }
+
+    fn dispatch(&mut self, event: Event) {
+        if self.muted {
+            return;
+        }
+        let mut dirs = event
+            .paths
+            .iter()
+            .map(|path| dir_of(path))
+            .collect::<Vec<_>>();
+        dirs.dedup();
+        for dir in self.subscriptions.values_mut() {
+            if dir.paths.iter().any(|path| dirs.contains(path)) {
+                dir.queue.push_back(event.clone());
+            }
+        }
+    }
+}
+
+impl Shared {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    #[inline]
+    fn now(&self, state: &State) -> Instant {
+        self.base + state.elapsed
+    }
+
+    /// Block the calling watcher thread until it's ready to continue.
+    fn park<'a>(
+        &self,
+        mut state: MutexGuard<'a, State>,
+        deadline: Instant,
+        subscription: Option<usize>,
+    ) -> MutexGuard<'a, State> {
+        let id = state.next_id;
+        state.next_id += 1;
+        state.parked.insert(
+            id,
+            Parked {
+                deadline,
+                subscription,
+            },
+        );
+        // let settle() re-check.
+        self.cond.notify_all();
+        loop {
+            state = self.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+            let now = self.now(&state);
+            if state.is_ready(&state.parked[&id], now) {
+                break;
+            }
+        }
+        state.parked.remove(&id);
+        state
+    }
+
+    /// Wait until every running watcher is parked with nothing left to do.
+    fn settle<'a>(&self, mut state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        loop {
+            let now = self.now(&state);
+            if state.parked.len() >= state.watchers
+                && state
+                    .parked
+                    .values()
+                    .all(|parked| !state.is_ready(parked, now))
+            {
+                return state;
+            }
+            state = self.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Env handed to the watchers.
+struct Virtual(Arc<Shared>);
+
+impl Env for Virtual {
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + Send>> {
+        match self.0.lock().files.get(&key(path)) {
+            Some(file) => Ok(Box::new(Cursor::new(file.data.clone()))),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not found", path.to_string_lossy()),
+            )),
+        }
+    }
+
+    fn stamp(&self, path: &Path) -> Stamp {
+        self.0
+            .lock()
+            .files
+            .get(&key(path))
+            .map(|file| (Some(UNIX_EPOCH + file.modified), file.data.len() as u64))
+    }
+
+    fn metadata(&self, path: &Path) -> Option<Fmeta> {
+        self.0.lock().files.get(&key(path)).map(|file| Fmeta {
+            mode: Some(file.mode),
+            uid: Some(file.uid),
+            gid: Some(file.gid),
+            readonly: file.mode & 0o222 == 0,
+            modified: Some(UNIX_EPOCH + file.modified),
+            len: file.data.len() as u64,
+        })
+    }
+
+    #[cfg(any(feature = "live_json", feature = "live_ron"))]
+    fn write(&self, path: &Path, data: &[u8]) -> io::Result<()> {
+        // written by a watcher's consumer, which can't wait for the watchers to settle.
+        self.0.lock().write(key(path), data);
+        self.0.cond.notify_all();
+        Ok(())
+    }
+
+    fn read_dir(&self, dir: &Path) -> Vec<PathBuf> {
+        let dir = key(dir);
+        let state = self.0.lock();
+        state
+            .files
+            .keys()
+            .filter(|path| dir_of(path) == dir)
+            .cloned()
+            .collect()
+    }
+
+    fn now(&self) -> Instant {
+        let state = self.0.lock();
+        self.0.now(&state)
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let mut state = self.0.lock();
+        while self.0.now(&state) < deadline {
+            state = self.0.park(state, deadline, None);
+        }
+    }
+
+    fn subscribe(&self, dir: &Path) -> Result<Box<dyn Subscription>> {
+        let mut state = self.0.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.subscriptions.insert(
+            id,
+            Dir {
+                paths: vec![key(dir)],
+                queue: VecDeque::new(),
+            },
+        );
+        Ok(Box::new(VirtualDir {
+            shared: self.0.clone(),
+            id,
+        }))
+    }
+
+    fn enter(&self) {
+        let mut state = self.0.lock();
+        state.watchers += 1;
+        state.spawned += 1;
+        self.0.cond.notify_all();
+    }
+
+    fn leave(&self) {
+        let mut state = self.0.lock();
+        state.watchers -= 1;
+        self.0.cond.notify_all();
+    }
+}
+
+struct VirtualDir {
+    shared: Arc<Shared>,
+    id: usize,
+}
+
+impl Subscription for VirtualDir {
+    fn recv_until(&mut self, deadline: Instant) -> Option<Result<Event>> {
+        let shared = &self.shared;
+        let mut state = shared.lock();
+        loop {
+            if let Some(event) = state
+                .subscriptions
+                .get_mut(&self.id)
+                .and_then(|dir| dir.queue.pop_front())
+            {
+                return Some(Ok(event));
+            }
+            if shared.now(&state) >= deadline {
+                return None;
+            }
+            state = shared.park(state, deadline, Some(self.id));
+        }
+    }
+
+    fn watch(&mut self, dir: &Path) -> Result<()> {
+        if let Some(subscription) = self.shared.lock().subscriptions.get_mut(&self.id) {
+            subscription.paths.push(key(dir));
+        }
+        Ok(())
+    }
+
+    fn unwatch(&mut self, dir: &Path) {
+        let dir = key(dir);
+        if let Some(subscription) = self.shared.lock().subscriptions.get_mut(&self.id) {
+            subscription.paths.retain(|path| *path != dir);
+        }
+    }
+}
+
+impl Drop for VirtualDir {
+    fn drop(&mut self) {
+        self.shared.lock().subscriptions.remove(&self.id);
+    }
+}
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualFs {
+    pub fn new() -> VirtualFs {
+        Self {
+            shared: Arc::new(Shared {
+                base: Instant::now(),
+                state: Mutex::new(State::default()),
+                cond: Condvar::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn env(&self) -> Arc<dyn Env> {
+        Arc::new(Virtual(self.shared.clone()))
+    }
+
+    /// Create or overwrite the file, emitting Create (if new) and Modify events.
+    pub fn write<P: AsRef<Path>, D: AsRef<[u8]>>(&self, path: P, data: D) {
+        let mut state = self.shared.lock();
+        state.write(key(path.as_ref()), data.as_ref());
+        self.after(state);
+    }
+
+    /// Rename (atomically replacing the destination), emitting the From, To and Both events like inotify does.
+    pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(&self, from: P, to: Q) {
+        let (from, to) = (key(from.as_ref()), key(to.as_ref()));
+        let mut state = self.shared.lock();
+        if let Some(file) = state.files.remove(&from) {
+            state.files.insert(to.clone(), file);
+            state.dispatch(
+                Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::From)))
+                    .add_path(from.clone()),
+            );
+            state.dispatch(
+                Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::To)))
+                    .add_path(to.clone()),
+            );
+            state.dispatch(
+                Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+                    .add_path(from)
+                    .add_path(to),
+            );
+        }
+        self.after(state);
+    }
+
+    /// Remove the file, emitting a Remove event.
+    pub fn remove<P: AsRef<Path>>(&self, path: P) {
+        let path = key(path.as_ref());
+        let mut state = self.shared.lock();
+        if state.files.remove(&path).is_some() {
+            state.dispatch(Event::new(EventKind::Remove(RemoveKind::File)).add_path(path));
+        }
+        self.after(state);
+    }
+
+    /// Change the permission bits, emitting a metadata event.
+    pub fn chmod<P: AsRef<Path>>(&self, path: P, mode: u32) {
+        self.set_metadata(path.as_ref(), MetadataKind::Permissions, |file| {
+            file.mode = mode
+        });
+    }
+
+    /// Change the owner and group, emitting a metadata event.
+    pub fn chown<P: AsRef<Path>>(&self, path: P, uid: u32, gid: u32) {
+        self.set_metadata(path.as_ref(), MetadataKind::Ownership, |file| {
+            file.uid = uid;
+            file.gid = gid;
+        });
+    }
+
+    fn set_metadata<F: FnOnce(&mut VFile)>(&self, path: &Path, kind: MetadataKind, f: F) {
+        let path = key(path);
+        let mut state = self.shared.lock();
+        if let Some(file) = state.files.get_mut(&path) {
+            f(file);
+            state
+                .dispatch(Event::new(EventKind::Modify(ModifyKind::Metadata(kind))).add_path(path));
+        }
+        self.after(state);
+    }
+
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>> {
+        self.shared
+            .lock()
+            .files
+            .get(&key(path.as_ref()))
+            .map(|file| file.data.clone())
+    }
+
+    /// Stop (or resume) emitting events, like a network mount where native notifications never fire.
+    pub fn mute(&self, muted: bool) {
+        self.shared.lock().muted = muted;
+    }
+
+    /// Virtual time elapsed since the filesystem was created.
+    pub fn elapsed(&self) -> Duration {
+        self.shared.lock().elapsed
+    }
+
+    /// Move the clock forward, stopping at every deadline in between so retries and timeouts fire in order.
+    pub fn advance(&self, duration: Duration) {
+        let shared = &self.shared;
+        let mut state = shared.settle(shared.lock());
+        let target = shared.now(&state) + duration;
+        loop {
+            let now = shared.now(&state);
+            let next = state
+                .parked
+                .values()
+                .map(|parked| parked.deadline)
+                .filter(|deadline| *deadline > now && *deadline <= target)
+                .min()
+                .unwrap_or(target);
+            state.elapsed = next - shared.base;
+            shared.cond.notify_all();
+            state = shared.settle(state);
+            if next >= target {
+                break;
+            }
+        }
+    }
+
+    /// Block until every running watcher is waiting with nothing left to process.
+    pub fn settle(&self) {
+        drop(self.shared.settle(self.shared.lock()));
+    }
+
+    /// Block until at least the given number of watchers have been spawned, then settle.
+    pub fn wait_watchers(&self, spawned: usize) {
+        let shared = &self.shared;
+        let mut state = shared.lock();
+        while state.spawned < spawned {
+            state = shared.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        drop(shared.settle(state));
+    }
+
+    /// Number of watcher threads still running.
+    pub fn watchers(&self) -> usize {
+        self.shared.lock().watchers
+    }
+
+    fn after(&self, state: MutexGuard<'_, State>) {
+        self.shared.cond.notify_all();
+        drop(self.shared.settle(state));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Backend, Fstate, RetryPolicy, Watch};
+    use cfg_if::cfg_if;
+    cfg_if! {
+       if #[cfg(feature = "crossbeam_channel")] {
+           use crossbeam_channel::unbounded as channel;
+       } else if #[cfg(feature = "flume_channel")] {
+           use flume::unbounded as channel;
+       } else {
+           use std::sync::mpsc::channel;
+       }
+    }
+
+    const TIMEOUT: Duration = Duration::from_millis(700);
+
+    #[test]
+    fn test_timeout_not_found() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("assets/config.json");
+        fs.write(&file, "{}");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new().set_fs(&fs).set_timeout(TIMEOUT);
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        fs.write(&file, "{\"a\": 1}");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        // same data again, nothing to report.
+        fs.write(&file, "{\"a\": 1}");
+        assert!(rx.try_recv().is_err());
+        let stats = watch.stats();
+        assert_eq!((stats.watched_files, stats.events), (1, 2));
+        assert_eq!((stats.changes, stats.suppressed), (1, 1));
+
+        // gone for good: 8 retries, timeout / 7 apart.
+        fs.remove(&file);
+        fs.advance(TIMEOUT);
+        assert!(rx.try_recv().is_err());
+        fs.advance(TIMEOUT / 7);
+        assert!(matches!(rx.try_recv(), Ok(Fstate::NotFound(_))));
+        assert_eq!(fs.watchers(), 0);
+        let stats = watch.stats();
+        assert_eq!((stats.watched_files, stats.retries), (0, 8));
+    }
+
+    #[test]
+    fn test_retry_recovers() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("out/build.ron");
+        fs.write(&file, "(a: 1)");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new()
+            .set_fs(&fs)
+            .set_retry_policy(RetryPolicy::exponential(Duration::from_secs(1)).max_attempts(3));
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        // retries after 1s, 2s and 4s, the file is back before the last one.
+        fs.remove(&file);
+        fs.advance(Duration::from_secs(3));
+        fs.mute(true);
+        fs.write(&file, "(a: 2)");
+        fs.mute(false);
+        assert!(rx.try_recv().is_err());
+        fs.advance(Duration::from_secs(4));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        assert_eq!(fs.watchers(), 1);
+    }
+
+    #[test]
+    fn test_rename_over_and_persistent() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("config.json");
+        fs.write(&file, "{}");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new()
+            .set_fs(&fs)
+            .set_timeout(TIMEOUT)
+            .set_persistent(Some(Duration::from_secs(10)));
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        // swap files are ignored, the rename over the file is reported once.
+        fs.write("config.json.swp", "{\"a\": 1}");
+        fs.write("config.json.tmp", "{\"a\": 1}");
+        fs.rename("config.json.tmp", &file);
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        assert!(rx.try_recv().is_err());
+
+        fs.remove(&file);
+        fs.advance(TIMEOUT + TIMEOUT / 7);
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Removed(_))));
+        fs.advance(Duration::from_secs(5));
+        fs.write(&file, "{\"a\": 2}");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Created(_))));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+
+        fs.remove(&file);
+        fs.advance(TIMEOUT + TIMEOUT / 7);
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Removed(_))));
+        fs.advance(Duration::from_secs(10));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::NotFound(_))));
+        assert_eq!(fs.watchers(), 0);
+    }
+
+    #[test]
+    fn test_auto_falls_back_to_polling() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("/mnt/nfs/settings.ron");
+        fs.write(&file, "(a: 1)");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new().set_fs(&fs).set_backend(Backend::Auto);
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        // no native events on this mount, caught by the next probe.
+        fs.mute(true);
+        fs.advance(Duration::from_millis(500));
+        fs.write(&file, "(a: 22)");
+        assert!(rx.try_recv().is_err());
+        fs.advance(Duration::from_millis(500));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+
+        // still nothing native until the next probe, polling from now on.
+        fs.advance(Duration::from_millis(100));
+        fs.write(&file, "(a: 333)");
+        fs.advance(Duration::from_secs(1));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+
+        fs.mute(false);
+        fs.write(&file, "(a: 4444)");
+        assert!(rx.try_recv().is_err());
+        fs.advance(Duration::from_secs(1));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+    }
+
+    #[test]
+    fn test_auto_keeps_native() {
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("/home/settings.ron");
+        fs.write(&file, "(a: 1)");
+
+        let (tx, rx) = channel();
+        let watch = Watch::new().set_fs(&fs).set_backend(Backend::Auto);
+        assert!(watch.single_file(&file, tx).unwrap());
+        fs.wait_watchers(1);
+
+        // one event lost before the first native one, caught by the probe.
+        fs.mute(true);
+        fs.write(&file, "(a: 22)");
+        fs.mute(false);
+        fs.advance(Duration::from_secs(1));
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+
+        // native events arrive before the next probe, no fallback.
+        fs.write(&file, "(a: 333)");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+        fs.advance(Duration::from_secs(3));
+        assert!(rx.try_recv().is_err());
+        fs.write(&file, "(a: 4444)");
+        assert!(matches!(rx.try_recv(), Ok(Fstate::Changed(_))));
+    }
+
+    #[cfg(feature = "live_json")]
+    #[test]
+    fn test_live_json() {
+        use crate::LiveJson;
+        use std::thread::spawn;
+
+        #[derive(serde::Deserialize)]
+        struct Config {
+            a: i32,
+        }
+        impl LiveJson for Config {}
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("config.json");
+        fs.write(&file, "{\"a\": 1}");
+        let watch = Watch::new().set_fs(&fs);
+
+        let mut config = Config { a: 0 };
+        config.reinit_from_json_with(&file, &watch);
+        assert_eq!(config.a, 1);
+
+        let (reload_file, reload_watch) = (file.clone(), watch.clone());
+        let reload = spawn(move || {
+            assert!(config
+                .reload_from_json_with(&reload_file, &reload_watch)
+                .unwrap());
+            config
+        });
+        fs.wait_watchers(1);
+        fs.write(&file, "{\"a\": 2}");
+        assert_eq!(reload.join().unwrap().a, 2);
+    }
+
+    #[cfg(feature = "live_ron")]
+    #[test]
+    fn test_live_ron() {
+        use crate::LiveRon;
+        use std::thread::spawn;
+
+        #[derive(serde::Deserialize)]
+        struct Config {
+            a: i32,
+        }
+        impl LiveRon for Config {}
+
+        let fs = VirtualFs::new();
+        let file = PathBuf::from("config.ron");
+        fs.write(&file, "(a: 1)");
+        let watch = Watch::new().set_fs(&fs);
+
+        let mut config = Config { a: 0 };
+        config.reinit_from_ron_with(&file, &watch);
+        assert_eq!(config.a, 1);
+
+        let reload = |mut config: Config| {
+            let (file, watch) = (file.clone(), watch.clone());
+            spawn(move || {
+                assert!(config.reload_from_ron_with(&file, &watch).unwrap());
+                config
+            })
+        };
+        // not valid ron, the previous value stays.
+        let broken = reload(config);
+        fs.wait_watchers(1);
+        fs.write(&file, "(a: ");
+        let config = broken.join().unwrap();
+        assert_eq!(config.a, 1);
+
+        let fixed = reload(config);
+        fs.wait_watchers(2);
+        fs.write(&file, "(a: 2)");
+        assert_eq!(fixed.join().unwrap().a, 2);
+    }
+}